@@ -1,19 +1,22 @@
+// `is_some_and` would raise the minimum supported Rust version to 1.70
+#![allow(clippy::unnecessary_map_or)]
+
 #[macro_use]
 extern crate derivative;
 #[macro_use]
-extern crate failure_derive;
-#[macro_use]
 extern crate serde_derive;
 
 use chrono::prelude::*;
 use chrono::Duration;
-use failure::{format_err, Error};
 use serde::de::{self, Deserialize, Deserializer};
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
+use std::hash::Hasher;
 use std::path::Path;
 use std::sync::Arc;
+use thiserror::Error;
 
 #[cfg(feature = "read-url")]
 use std::io::Read;
@@ -22,12 +25,67 @@ pub trait Id {
     fn id(&self) -> &str;
 }
 
-#[derive(Fail, Debug)]
-#[fail(display = "The id {} is not known", id)]
+#[derive(Error, Debug)]
+#[error("The id {id} is not known")]
 pub struct ReferenceError {
     pub id: String,
 }
 
+#[derive(Error, Debug)]
+pub enum StopTimeError {
+    #[error("The trip {trip_id} is not loaded, trips must be read before stop_times")]
+    TripNotLoaded { trip_id: String },
+}
+
+#[derive(Error, Debug)]
+pub enum GtfsError {
+    #[error("Impossible to read the file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Impossible to parse the csv: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Impossible to read the zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Invalid date {date}, expected YYYYMMDD")]
+    InvalidDate {
+        date: String,
+        #[source]
+        source: chrono::ParseError,
+    },
+    /// `source` is `None` when the time does not have three parts
+    #[error("Invalid time {time}")]
+    InvalidTime {
+        time: String,
+        #[source]
+        source: Option<std::num::ParseIntError>,
+    },
+    #[error(transparent)]
+    Reference(#[from] ReferenceError),
+    #[error(transparent)]
+    StopTime(#[from] StopTimeError),
+    #[error("Missing file {0}")]
+    MissingFile(String),
+    #[error("Missing files {}", .0.join(", "))]
+    MissingFiles(Vec<String>),
+    #[error("Invalid file name {0}")]
+    InvalidFileName(String),
+    #[error("Invalid route_type {route_type} for the route {route_id}")]
+    InvalidRouteType { route_id: String, route_type: u16 },
+    #[error("The shape {shape_id} has several points with the sequence {sequence}")]
+    DuplicateShapePoint { shape_id: String, sequence: usize },
+    #[error("Invalid language tag {0}")]
+    InvalidLanguageTag(String),
+    #[error("Invalid color {0}")]
+    InvalidColor(String),
+    #[error("Invalid polyline {0}")]
+    InvalidPolyline(String),
+    #[cfg(feature = "read-url")]
+    #[error("Impossible to download the feed: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[cfg(feature = "json")]
+    #[error("Impossible to serialize the feed: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum LocationType {
     StopPoint = 0,
@@ -35,6 +93,15 @@ pub enum LocationType {
     StationEntrance = 2,
 }
 
+impl serde::Serialize for LocationType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
 impl Default for LocationType {
     fn default() -> LocationType {
         LocationType::StopPoint
@@ -63,6 +130,75 @@ impl Default for RouteType {
     }
 }
 
+impl RouteType {
+    /// Whether the value is one of the basic types or a known extended route type
+    pub fn is_known(self) -> bool {
+        match self {
+            RouteType::Other(i) => matches!(
+                i,
+                11 | 12
+                    | 100..=299
+                    | 400..=499
+                    | 700..=1599
+                    | 1700..=1799
+            ),
+            _ => true,
+        }
+    }
+
+    /// Rail, subway, tramway, funicular and cable car, including the extended rail codes
+    pub fn is_rail_like(self) -> bool {
+        match self {
+            RouteType::Tramway
+            | RouteType::Subway
+            | RouteType::Rail
+            | RouteType::CableCar
+            | RouteType::Funicular => true,
+            RouteType::Other(i) => {
+                matches!(i, 12 | 100..=199 | 400..=499 | 900..=999 | 1400..=1499)
+            }
+            _ => false,
+        }
+    }
+
+    /// Bus, trolleybus and coach, including the extended codes
+    pub fn is_bus_like(self) -> bool {
+        match self {
+            RouteType::Bus => true,
+            RouteType::Other(i) => matches!(i, 11 | 200..=299 | 700..=799 | 800),
+            _ => false,
+        }
+    }
+
+    /// Ferry and the extended water transport codes
+    pub fn is_water(self) -> bool {
+        match self {
+            RouteType::Ferry => true,
+            RouteType::Other(i) => matches!(i, 1000..=1099 | 1200..=1299),
+            _ => false,
+        }
+    }
+}
+
+impl serde::Serialize for RouteType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u16(match self {
+            RouteType::Tramway => 0,
+            RouteType::Subway => 1,
+            RouteType::Rail => 2,
+            RouteType::Bus => 3,
+            RouteType::Ferry => 4,
+            RouteType::CableCar => 5,
+            RouteType::Gondola => 6,
+            RouteType::Funicular => 7,
+            RouteType::Other(i) => *i,
+        })
+    }
+}
+
 impl<'de> ::serde::Deserialize<'de> for RouteType {
     fn deserialize<D>(deserializer: D) -> Result<RouteType, D::Error>
     where
@@ -85,7 +221,7 @@ impl<'de> ::serde::Deserialize<'de> for RouteType {
 
 #[derive(Derivative)]
 #[derivative(Default(bound = ""))]
-#[derive(Debug, Deserialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
 pub enum PickupDropOffType {
     #[derivative(Default)]
     #[serde(rename = "0")]
@@ -98,7 +234,33 @@ pub enum PickupDropOffType {
     CoordinateWithDriver,
 }
 
-#[derive(Debug, Deserialize)]
+impl PickupDropOffType {
+    /// Whether the rider must call the agency or tell the driver beforehand
+    pub fn requires_arrangement(self) -> bool {
+        match self {
+            PickupDropOffType::ArrangeByPhone | PickupDropOffType::CoordinateWithDriver => true,
+            PickupDropOffType::Regular | PickupDropOffType::NotAvailable => false,
+        }
+    }
+}
+
+/// Whether riders can board or alight anywhere along the vehicle's path
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum ContinuousPickupDropOff {
+    #[serde(rename = "0")]
+    Continuous,
+    #[derivative(Default)]
+    #[serde(rename = "1")]
+    NotAvailable,
+    #[serde(rename = "2")]
+    ArrangeByPhone,
+    #[serde(rename = "3")]
+    CoordinateWithDriver,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Calendar {
     #[serde(rename = "service_id")]
     pub id: String,
@@ -116,9 +278,15 @@ pub struct Calendar {
     pub saturday: bool,
     #[serde(deserialize_with = "deserialize_bool")]
     pub sunday: bool,
-    #[serde(deserialize_with = "deserialize_date")]
+    #[serde(
+        deserialize_with = "deserialize_date",
+        serialize_with = "serialize_date"
+    )]
     pub start_date: NaiveDate,
-    #[serde(deserialize_with = "deserialize_date")]
+    #[serde(
+        deserialize_with = "deserialize_date",
+        serialize_with = "serialize_date"
+    )]
     pub end_date: NaiveDate,
 }
 
@@ -148,7 +316,53 @@ pub enum Availability {
 
 impl Calendar {
     pub fn valid_weekday(&self, date: NaiveDate) -> bool {
-        match date.weekday() {
+        self.runs_on(date.weekday())
+    }
+
+    pub fn active_weekdays(&self) -> Vec<Weekday> {
+        [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+            Weekday::Sun,
+        ]
+        .iter()
+        .cloned()
+        .filter(|day| self.runs_on(*day))
+        .collect()
+    }
+
+    pub fn runs_every_day(&self) -> bool {
+        self.active_weekdays().len() == 7
+    }
+
+    /// Describes the weekly pattern, like "Monday–Friday", "Weekends" or "Daily"
+    pub fn weekday_summary(&self) -> String {
+        let days = self.active_weekdays();
+        let is_range = days
+            .windows(2)
+            .all(|pair| pair[0].number_from_monday() + 1 == pair[1].number_from_monday());
+        match days.as_slice() {
+            [] => "No weekly service".to_owned(),
+            _ if days.len() == 7 => "Daily".to_owned(),
+            [Weekday::Sat, Weekday::Sun] => "Weekends".to_owned(),
+            [day] => weekday_name(*day).to_owned(),
+            [first, .., last] if is_range && days.len() > 2 => {
+                format!("{}–{}", weekday_name(*first), weekday_name(*last))
+            }
+            _ => days
+                .iter()
+                .map(|day| weekday_name(*day))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    fn runs_on(&self, weekday: Weekday) -> bool {
+        match weekday {
             Weekday::Mon => self.monday,
             Weekday::Tue => self.tuesday,
             Weekday::Wed => self.wednesday,
@@ -160,15 +374,30 @@ impl Calendar {
     }
 }
 
-#[derive(Debug, Deserialize)]
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CalendarDate {
     pub service_id: String,
-    #[serde(deserialize_with = "deserialize_date")]
+    #[serde(
+        deserialize_with = "deserialize_date",
+        serialize_with = "serialize_date"
+    )]
     pub date: NaiveDate,
     pub exception_type: u8,
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct Stop {
     #[serde(rename = "stop_id")]
     pub id: String,
@@ -184,11 +413,10 @@ pub struct Stop {
     )]
     pub location_type: LocationType,
     pub parent_station: Option<String>,
-    #[serde(deserialize_with = "de_with_trimed_float")]
-    #[serde(rename = "stop_lon")]
+    pub zone_id: Option<String>,
+    #[serde(rename = "stop_lon", alias = "stop_longitude")]
     pub longitude: f64,
-    #[serde(deserialize_with = "de_with_trimed_float")]
-    #[serde(rename = "stop_lat")]
+    #[serde(rename = "stop_lat", alias = "stop_latitude")]
     pub latitude: f64,
     #[serde(rename = "stop_timezone")]
     pub timezone: Option<String>,
@@ -208,31 +436,73 @@ impl fmt::Display for Stop {
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
-struct StopTimeGtfs {
-    trip_id: String,
-    #[serde(deserialize_with = "deserialize_time")]
-    pub arrival_time: u32,
-    #[serde(deserialize_with = "deserialize_time")]
-    pub departure_time: u32,
-    stop_id: String,
-    stop_sequence: u16,
-    pickup_type: Option<PickupDropOffType>,
-    drop_off_type: Option<PickupDropOffType>,
+/// A line of `stop_times.txt`, referencing its trip and stop by id
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct StopTimeRow {
+    pub trip_id: String,
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub arrival_time: Option<u32>,
+    #[serde(
+        deserialize_with = "deserialize_optional_time",
+        serialize_with = "serialize_optional_time",
+        default
+    )]
+    pub departure_time: Option<u32>,
+    pub stop_id: String,
+    pub stop_sequence: u16,
+    pub pickup_type: Option<PickupDropOffType>,
+    pub drop_off_type: Option<PickupDropOffType>,
+    pub shape_dist_traveled: Option<f32>,
+    pub continuous_pickup: Option<ContinuousPickupDropOff>,
+    pub continuous_drop_off: Option<ContinuousPickupDropOff>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Serialize, Default, Clone, PartialEq)]
 pub struct StopTime {
-    pub arrival_time: u32,
+    pub arrival_time: Option<u32>,
+    #[serde(serialize_with = "serialize_stop_id")]
     pub stop: Arc<Stop>,
-    pub departure_time: u32,
+    pub departure_time: Option<u32>,
     pub pickup_type: Option<PickupDropOffType>,
     pub drop_off_type: Option<PickupDropOffType>,
     pub stop_sequence: u16,
+    pub shape_dist_traveled: Option<f32>,
+    /// Overrides `Route::continuous_pickup` from this stop to the next one
+    pub continuous_pickup: Option<ContinuousPickupDropOff>,
+    /// Overrides `Route::continuous_drop_off` from this stop to the next one
+    pub continuous_drop_off: Option<ContinuousPickupDropOff>,
 }
 
 impl StopTime {
-    fn from(stop_time_gtfs: &StopTimeGtfs, stop: Arc<Stop>) -> Self {
+    /// Arrival as a wall-clock time
+    ///
+    /// `None` from 24:00:00, as a `NaiveTime` cannot represent the times of the next day used by GTFS
+    pub fn arrival_naive_time(&self) -> Option<NaiveTime> {
+        self.arrival_time.and_then(seconds_to_naive_time)
+    }
+
+    /// Departure as a wall-clock time
+    ///
+    /// `None` from 24:00:00, as a `NaiveTime` cannot represent the times of the next day used by GTFS
+    pub fn departure_naive_time(&self) -> Option<NaiveTime> {
+        self.departure_time.and_then(seconds_to_naive_time)
+    }
+
+    /// Whether riders can board, possibly after an arrangement (see `PickupDropOffType::requires_arrangement`)
+    pub fn allows_pickup(&self) -> bool {
+        self.pickup_type != Some(PickupDropOffType::NotAvailable)
+    }
+
+    /// Whether riders can alight, possibly after an arrangement (see `PickupDropOffType::requires_arrangement`)
+    pub fn allows_drop_off(&self) -> bool {
+        self.drop_off_type != Some(PickupDropOffType::NotAvailable)
+    }
+
+    fn from(stop_time_gtfs: &StopTimeRow, stop: Arc<Stop>) -> Self {
         Self {
             arrival_time: stop_time_gtfs.arrival_time,
             departure_time: stop_time_gtfs.departure_time,
@@ -240,11 +510,122 @@ impl StopTime {
             pickup_type: stop_time_gtfs.pickup_type,
             drop_off_type: stop_time_gtfs.drop_off_type,
             stop_sequence: stop_time_gtfs.stop_sequence,
+            shape_dist_traveled: stop_time_gtfs.shape_dist_traveled,
+            continuous_pickup: stop_time_gtfs.continuous_pickup,
+            continuous_drop_off: stop_time_gtfs.continuous_drop_off,
+        }
+    }
+}
+
+/// A color given as a six digits hexadecimal string, like `route_color`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    pub const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+
+    /// Relative luminance as defined by WCAG, from 0 for black to 1 for white
+    pub fn relative_luminance(self) -> f64 {
+        let channel = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
+
+    /// WCAG contrast ratio between two colors, from 1 to 21
+    pub fn contrast_ratio(self, other: Color) -> f64 {
+        let (l1, l2) = (self.relative_luminance(), other.relative_luminance());
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = GtfsError;
+
+    fn from_str(s: &str) -> Result<Color, GtfsError> {
+        let invalid = || GtfsError::InvalidColor(s.to_owned());
+        let hex = s.trim();
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
         }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Color {
+            r: component(0)?,
+            g: component(2)?,
+            b: component(4)?,
+        })
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+impl serde::Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> ::serde::Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Color, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
+fn default_route_color() -> Color {
+    Color::WHITE
+}
+
+/// Parses a color, `default` replacing an empty or invalid one; the invalid ones are reported by `Gtfs::read_routes_checking_colors`
+fn deserialize_lenient_color<'de, D>(deserializer: D, default: Color) -> Result<Color, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    Ok(s.and_then(|s| s.parse().ok()).unwrap_or(default))
+}
+
+fn deserialize_route_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_lenient_color(deserializer, default_route_color())
+}
+
+fn deserialize_route_text_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_lenient_color(deserializer, Color::default())
+}
+
+#[derive(Derivative)]
+#[derivative(Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Route {
     #[serde(rename = "route_id")]
     pub id: String,
@@ -254,7 +635,26 @@ pub struct Route {
     pub long_name: String,
     pub route_type: RouteType,
     pub agency_id: Option<String>,
+    #[serde(alias = "route_sort_order")]
     pub route_order: Option<u32>,
+    pub network_id: Option<String>,
+    #[serde(
+        rename = "route_color",
+        default = "default_route_color",
+        deserialize_with = "deserialize_route_color"
+    )]
+    #[derivative(Default(value = "default_route_color()"))]
+    pub color: Color,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub continuous_pickup: ContinuousPickupDropOff,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub continuous_drop_off: ContinuousPickupDropOff,
+    #[serde(
+        rename = "route_text_color",
+        default,
+        deserialize_with = "deserialize_route_text_color"
+    )]
+    pub text_color: Color,
 }
 
 impl Id for Route {
@@ -273,13 +673,20 @@ impl fmt::Display for Route {
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct Trip {
     #[serde(rename = "trip_id")]
     pub id: String,
     pub service_id: String,
     pub route_id: String,
-    #[serde(skip)]
+    pub trip_short_name: Option<String>,
+    pub shape_id: Option<String>,
+    pub block_id: Option<String>,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub wheelchair_accessible: Availability,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub bikes_allowed: Availability,
+    #[serde(skip_deserializing)]
     pub stop_times: Vec<StopTime>,
 }
 
@@ -289,6 +696,46 @@ impl Id for Trip {
     }
 }
 
+impl Trip {
+    pub fn stop_count(&self) -> usize {
+        self.stop_times.len()
+    }
+
+    /// Finds the stop time with a given `stop_sequence`
+    ///
+    /// Relies on the stop times being sorted, which is the case unless disabled in `GtfsReader`
+    pub fn stop_time_at_sequence(&self, stop_sequence: u16) -> Option<&StopTime> {
+        self.stop_times
+            .binary_search_by_key(&stop_sequence, |st| st.stop_sequence)
+            .ok()
+            .map(|index| &self.stop_times[index])
+    }
+
+    /// Departure time from the first stop
+    pub fn start_time(&self) -> Option<u32> {
+        self.stop_times
+            .first()
+            .and_then(|st| st.departure_time.or(st.arrival_time))
+    }
+
+    /// Arrival time at the last stop
+    pub fn end_time(&self) -> Option<u32> {
+        self.stop_times
+            .last()
+            .and_then(|st| st.arrival_time.or(st.departure_time))
+    }
+
+    /// Origin of the trip, relying on the stop times being sorted
+    pub fn first_stop(&self) -> Option<&Arc<Stop>> {
+        self.stop_times.first().map(|st| &st.stop)
+    }
+
+    /// Terminus of the trip, relying on the stop times being sorted
+    pub fn last_stop(&self) -> Option<&Arc<Stop>> {
+        self.stop_times.last().map(|st| &st.stop)
+    }
+}
+
 impl fmt::Display for Trip {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -299,440 +746,6032 @@ impl fmt::Display for Trip {
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
-pub struct Agency {
-    #[serde(rename = "agency_id")]
-    pub id: Option<String>,
-    #[serde(rename = "agency_name")]
-    pub name: String,
-    #[serde(rename = "agency_url")]
-    pub url: String,
-    #[serde(rename = "agency_timezone")]
-    pub timezone: String,
-    #[serde(rename = "agency_lang")]
-    pub lang: Option<String>,
-    #[serde(rename = "agency_phone")]
-    pub phone: Option<String>,
-    #[serde(rename = "agency_fare_url")]
-    pub fare_url: Option<String>,
-    #[serde(rename = "agency_email")]
-    pub email: Option<String>,
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Shape {
+    #[serde(rename = "shape_id")]
+    pub id: String,
+    #[serde(rename = "shape_pt_lat", default)]
+    pub latitude: f64,
+    #[serde(rename = "shape_pt_lon", default)]
+    pub longitude: f64,
+    #[serde(rename = "shape_pt_sequence")]
+    pub sequence: usize,
+    #[serde(rename = "shape_dist_traveled")]
+    pub dist_traveled: Option<f32>,
 }
 
-impl fmt::Display for Agency {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name)
+/// Unit of the `shape_dist_traveled` values of a feed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DistanceUnit {
+    Meters,
+    Kilometers,
+    Miles,
+    Feet,
+}
+
+impl DistanceUnit {
+    /// Length of one unit in meters
+    pub fn meters(self) -> f64 {
+        match self {
+            DistanceUnit::Meters => 1.0,
+            DistanceUnit::Kilometers => 1000.0,
+            DistanceUnit::Miles => 1609.344,
+            DistanceUnit::Feet => 0.3048,
+        }
     }
 }
 
-fn deserialize_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s = String::deserialize(deserializer)?;
-    NaiveDate::parse_from_str(&s, "%Y%m%d").map_err(serde::de::Error::custom)
+impl Id for Shape {
+    fn id(&self) -> &str {
+        &self.id
+    }
 }
 
-pub fn parse_time(s: &str) -> Result<u32, Error> {
-    let v: Vec<&str> = s.split(':').collect();
-    Ok(&v[0].parse()? * 3600u32 + &v[1].parse()? * 60u32 + &v[2].parse()?)
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum PaymentMethod {
+    #[derivative(Default)]
+    #[serde(rename = "0")]
+    Aboard,
+    #[serde(rename = "1")]
+    PreBoarding,
 }
 
-fn deserialize_time<'de, D>(deserializer: D) -> Result<u32, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: String = String::deserialize(deserializer)?;
-    parse_time(&s).map_err(de::Error::custom)
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum Transfers {
+    #[derivative(Default)]
+    #[serde(rename = "")]
+    Unlimited,
+    #[serde(rename = "0")]
+    NoTransfer,
+    #[serde(rename = "1")]
+    UniqueTransfer,
+    #[serde(rename = "2")]
+    TwoTransfers,
 }
 
-fn deserialize_location_type<'de, D>(deserializer: D) -> Result<LocationType, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: String = String::deserialize(deserializer)?;
-    Ok(match s.as_str() {
-        "1" => LocationType::StopArea,
-        "2" => LocationType::StationEntrance,
-        _ => LocationType::StopPoint,
-    })
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct FareAttribute {
+    #[serde(rename = "fare_id")]
+    pub id: String,
+    pub price: f64,
+    #[serde(rename = "currency_type")]
+    pub currency: String,
+    pub payment_method: PaymentMethod,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub transfers: Transfers,
+    pub agency_id: Option<String>,
+    pub transfer_duration: Option<u32>,
 }
 
-fn de_with_trimed_float<'de, D>(de: D) -> Result<f64, D::Error>
-where
-    D: ::serde::Deserializer<'de>,
-{
-    String::deserialize(de).and_then(|s| s.trim().parse().map_err(de::Error::custom))
+impl Id for FareAttribute {
+    fn id(&self) -> &str {
+        &self.id
+    }
 }
 
-pub fn de_with_empty_default<'de, T: Default, D>(de: D) -> Result<T, D::Error>
-where
-    D: ::serde::Deserializer<'de>,
-    T: ::serde::Deserialize<'de>,
-{
-    use serde::Deserialize;
-    Option::<T>::deserialize(de).map(|opt| opt.unwrap_or_else(Default::default))
+/// Active ISO 4217 currency codes
+const ISO_4217_CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP",
+    "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP",
+    "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS",
+    "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD",
+    "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD", "NGN",
+    "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR",
+    "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SLL",
+    "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VED", "VES",
+    "VND", "VUV", "WST", "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XCG", "XDR",
+    "XOF", "XPD", "XPF", "XPT", "XSU", "XTS", "XUA", "XXX", "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+impl FareAttribute {
+    pub fn has_valid_currency(&self) -> bool {
+        ISO_4217_CURRENCIES.contains(&self.currency.as_str())
+    }
+
+    /// `transfer_duration` as a duration, its ISO 8601 form being given by its `Display`
+    pub fn transfer_duration_as_chrono(&self) -> Option<Duration> {
+        self.transfer_duration
+            .map(|seconds| Duration::seconds(i64::from(seconds)))
+    }
 }
 
-fn default_location_type() -> LocationType {
-    LocationType::StopPoint
+/// Price of a fare, from the GTFS-Fares v2 `fare_products.txt`
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct FareProduct {
+    #[serde(rename = "fare_product_id")]
+    pub id: String,
+    #[serde(rename = "fare_product_name")]
+    pub name: Option<String>,
+    pub fare_media_id: Option<String>,
+    pub amount: f64,
+    pub currency: String,
 }
 
-#[derive(Default)]
-pub struct Gtfs {
-    pub read_duration: i64,
-    pub calendar: HashMap<String, Calendar>,
-    pub calendar_dates: HashMap<String, Vec<CalendarDate>>,
-    pub stops: HashMap<String, Arc<Stop>>,
-    pub routes: HashMap<String, Route>,
-    pub trips: HashMap<String, Trip>,
-    pub agencies: Vec<Agency>,
+impl Id for FareProduct {
+    fn id(&self) -> &str {
+        &self.id
+    }
 }
 
-impl Gtfs {
-    pub fn print_stats(&self) {
-        println!("GTFS data:");
-        println!("  Read in {} ms", self.read_duration);
-        println!("  Stops: {}", self.stops.len());
-        println!("  Routes: {}", self.routes.len());
-        println!("  Trips: {}", self.trips.len());
-        println!("  Agencies: {}", self.agencies.len());
+impl FareProduct {
+    pub fn has_valid_currency(&self) -> bool {
+        ISO_4217_CURRENCIES.contains(&self.currency.as_str())
     }
+}
 
-    pub fn new(path: &str) -> Result<Gtfs, Error> {
-        let now = Utc::now();
+/// Fare product applying to a leg, from the GTFS-Fares v2 `fare_leg_rules.txt`
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct FareLegRule {
+    pub leg_group_id: Option<String>,
+    pub network_id: Option<String>,
+    pub from_area_id: Option<String>,
+    pub to_area_id: Option<String>,
+    pub fare_product_id: String,
+}
+
+/// Figures about a shape computed without keeping its points, see `GtfsReader::summarize_shapes`
+#[derive(Debug, Default, Copy, Clone, Serialize)]
+pub struct ShapeSummary {
+    pub point_count: usize,
+    /// Length in meters, following the points in the order of the file
+    pub length: f64,
+    pub min_latitude: f64,
+    pub max_latitude: f64,
+    pub min_longitude: f64,
+    pub max_longitude: f64,
+    #[serde(skip)]
+    last_point: Option<(usize, f64, f64)>,
+}
+
+/// Ignores the last point read, only used while summarizing
+impl PartialEq for ShapeSummary {
+    fn eq(&self, other: &ShapeSummary) -> bool {
+        self.point_count == other.point_count
+            && self.length == other.length
+            && self.min_latitude == other.min_latitude
+            && self.max_latitude == other.max_latitude
+            && self.min_longitude == other.min_longitude
+            && self.max_longitude == other.max_longitude
+    }
+}
+
+impl ShapeSummary {
+    /// South-west and north-east corners, as (latitude, longitude)
+    pub fn bounding_box(&self) -> ((f64, f64), (f64, f64)) {
+        (
+            (self.min_latitude, self.min_longitude),
+            (self.max_latitude, self.max_longitude),
+        )
+    }
+
+    /// Summary of the points of a shape, in their order
+    fn of_points(points: &[Shape]) -> ShapeSummary {
+        let mut summary = ShapeSummary::default();
+        for point in points {
+            summary.add(point);
+        }
+        summary
+    }
+
+    /// Adds a point, returning false when its sequence is not after the previous one
+    fn add(&mut self, point: &Shape) -> bool {
+        let ordered = match self.last_point {
+            None => {
+                self.min_latitude = point.latitude;
+                self.max_latitude = point.latitude;
+                self.min_longitude = point.longitude;
+                self.max_longitude = point.longitude;
+                true
+            }
+            Some((sequence, latitude, longitude)) => {
+                self.length +=
+                    haversine_distance((latitude, longitude), (point.latitude, point.longitude));
+                self.min_latitude = self.min_latitude.min(point.latitude);
+                self.max_latitude = self.max_latitude.max(point.latitude);
+                self.min_longitude = self.min_longitude.min(point.longitude);
+                self.max_longitude = self.max_longitude.max(point.longitude);
+                sequence < point.sequence
+            }
+        };
+        self.point_count += 1;
+        self.last_point = Some((point.sequence, point.latitude, point.longitude));
+        ordered
+    }
+}
+
+/// Displays a duration for humans, like `1h`, `1h30min` or `45s`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.0.num_seconds();
+        if total == 0 {
+            return write!(f, "0s");
+        }
+        if total < 0 {
+            write!(f, "-")?;
+        }
+        let total = total.abs();
+        let (hours, minutes, seconds) = (total / 3600, total % 3600 / 60, total % 60);
+        if hours > 0 {
+            write!(f, "{}h", hours)?;
+        }
+        if minutes > 0 {
+            write!(f, "{}min", minutes)?;
+        }
+        if seconds > 0 {
+            write!(f, "{}s", seconds)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct FareRule {
+    pub fare_id: String,
+    pub route_id: Option<String>,
+    pub origin_id: Option<String>,
+    pub destination_id: Option<String>,
+    pub contains_id: Option<String>,
+}
+
+impl FareRule {
+    /// A rule matches when each of its fields is either empty or equal to the requested value
+    ///
+    /// As only the origin and destination zones are known, `contains_id` must be one of them
+    pub fn matches(
+        &self,
+        route_id: &str,
+        origin_zone: Option<&str>,
+        dest_zone: Option<&str>,
+    ) -> bool {
+        let field_matches = |field: &Option<String>, value: Option<&str>| match field {
+            None => true,
+            Some(f) => Some(f.as_str()) == value,
+        };
+        field_matches(&self.route_id, Some(route_id))
+            && field_matches(&self.origin_id, origin_zone)
+            && field_matches(&self.destination_id, dest_zone)
+            && (field_matches(&self.contains_id, origin_zone)
+                || field_matches(&self.contains_id, dest_zone))
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum BookingType {
+    #[derivative(Default)]
+    #[serde(rename = "0")]
+    RealTime,
+    #[serde(rename = "1")]
+    SameDay,
+    #[serde(rename = "2")]
+    PriorDay,
+}
+
+/// Booking conditions of a demand-responsive service, from the GTFS-Flex `booking_rules.txt`
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct BookingRule {
+    #[serde(rename = "booking_rule_id")]
+    pub id: String,
+    pub booking_type: BookingType,
+    /// Minimum number of minutes before the travel to book
+    pub prior_notice_duration_min: Option<u32>,
+    /// Maximum number of minutes before the travel to book
+    pub prior_notice_duration_max: Option<u32>,
+    /// Number of days before the travel by which the booking must be made
+    pub prior_notice_last_day: Option<u32>,
+    #[serde(deserialize_with = "deserialize_optional_time", default)]
+    pub prior_notice_last_time: Option<u32>,
+    /// Number of days before the travel from which the booking can be made
+    pub prior_notice_start_day: Option<u32>,
+    #[serde(deserialize_with = "deserialize_optional_time", default)]
+    pub prior_notice_start_time: Option<u32>,
+    pub prior_notice_service_id: Option<String>,
+    pub message: Option<String>,
+    pub pickup_message: Option<String>,
+    pub drop_off_message: Option<String>,
+    pub phone_number: Option<String>,
+    pub info_url: Option<String>,
+    pub booking_url: Option<String>,
+}
+
+impl Id for BookingRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum TransferType {
+    #[derivative(Default)]
+    #[serde(rename = "0")]
+    Recommended,
+    #[serde(rename = "1")]
+    Timed,
+    #[serde(rename = "2")]
+    MinTime,
+    #[serde(rename = "3")]
+    NotPossible,
+    /// Staying on board from `from_trip_id` to `to_trip_id`
+    #[serde(rename = "4")]
+    InSeat,
+    /// Alighting from `from_trip_id` is required to board `to_trip_id`
+    #[serde(rename = "5")]
+    InSeatNotAllowed,
+}
+
+/// Group of stops served by a demand-responsive service, from the GTFS-Flex `location_groups.txt`
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct LocationGroup {
+    #[serde(rename = "location_group_id")]
+    pub id: String,
+    #[serde(rename = "location_group_name")]
+    pub name: Option<String>,
+}
+
+impl Id for LocationGroup {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A line of `location_group_stops.txt`, adding a stop to a location group
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct LocationGroupStop {
+    pub location_group_id: String,
+    pub stop_id: String,
+}
+
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum ExactTimes {
+    /// Trips start around the headway, without a precise schedule
+    #[derivative(Default)]
+    #[serde(rename = "0")]
+    FrequencyBased,
+    /// Trips start exactly every `headway_secs` from `start_time`
+    #[serde(rename = "1")]
+    ScheduleBased,
+}
+
+/// Trip repeated every `headway_secs` between `start_time` and `end_time`, from `frequencies.txt`
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Frequency {
+    pub trip_id: String,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start_time: u32,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end_time: u32,
+    pub headway_secs: u32,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub exact_times: ExactTimes,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Transfer {
+    /// Required unless the transfer is between trips with `TransferType::InSeat` or `InSeatNotAllowed`
+    pub from_stop_id: Option<String>,
+    pub to_stop_id: Option<String>,
+    pub from_route_id: Option<String>,
+    pub to_route_id: Option<String>,
+    pub from_trip_id: Option<String>,
+    pub to_trip_id: Option<String>,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    pub transfer_type: TransferType,
+    /// Seconds needed to transfer
+    pub min_transfer_time: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Agency {
+    #[serde(rename = "agency_id")]
+    pub id: Option<String>,
+    #[serde(rename = "agency_name")]
+    pub name: String,
+    #[serde(rename = "agency_url")]
+    pub url: String,
+    #[serde(rename = "agency_timezone")]
+    pub timezone: String,
+    #[serde(rename = "agency_lang")]
+    pub lang: Option<String>,
+    #[serde(rename = "agency_phone")]
+    pub phone: Option<String>,
+    #[serde(rename = "agency_fare_url")]
+    pub fare_url: Option<String>,
+    #[serde(rename = "agency_email")]
+    pub email: Option<String>,
+    /// Columns that are not part of the specification, by name, with their values as written
+    #[serde(flatten, skip_deserializing)]
+    pub extra: HashMap<String, String>,
+}
+
+/// Columns of `agency.txt` defined by the specification
+const AGENCY_FIELDS: &[&str] = &[
+    "agency_id",
+    "agency_name",
+    "agency_url",
+    "agency_timezone",
+    "agency_lang",
+    "agency_phone",
+    "agency_fare_url",
+    "agency_email",
+];
+
+impl fmt::Display for Agency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Agency {
+    /// `agency_lang` when it is a well-formed language tag
+    pub fn language_tag(&self) -> Option<LanguageTag> {
+        self.lang.as_deref()?.parse().ok()
+    }
+}
+
+/// A BCP 47 language tag like `fr` or `en-US`, as used by `agency_lang`
+///
+/// Only the shape of the subtags is checked: a primary language of 2 or 3 letters,
+/// followed by subtags of 1 to 8 letters or digits
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+    /// The language without region or script, like `en` for `en-US`, in lowercase
+    pub fn primary_language(&self) -> String {
+        self.0
+            .split('-')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    }
+}
+
+impl std::str::FromStr for LanguageTag {
+    type Err = GtfsError;
+
+    fn from_str(s: &str) -> Result<LanguageTag, GtfsError> {
+        let tag = s.trim();
+        let mut subtags = tag.split('-');
+        let primary = subtags.next().unwrap_or_default();
+        let valid = (2..=3).contains(&primary.len())
+            && primary.chars().all(|c| c.is_ascii_alphabetic())
+            && subtags.all(|subtag| {
+                (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+        if valid {
+            Ok(LanguageTag(tag.to_owned()))
+        } else {
+            Err(GtfsError::InvalidLanguageTag(s.to_owned()))
+        }
+    }
+}
+
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_date(&s).map_err(de::Error::custom)
+}
+
+/// Parses a `YYYYMMDD` date, also accepting the `YYYY-MM-DD` form used by some feeds
+pub fn parse_date(s: &str) -> Result<NaiveDate, GtfsError> {
+    NaiveDate::parse_from_str(s, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+        .map_err(|source| GtfsError::InvalidDate {
+            date: s.to_owned(),
+            source,
+        })
+}
+
+fn serialize_date<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&date.format("%Y%m%d").to_string())
+}
+
+/// Formats seconds since midnight as `HH:MM:SS`, hours going past 24 for the next day
+pub fn format_time(seconds: u32) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn serialize_optional_time<S>(time: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match time {
+        Some(time) => serializer.serialize_str(&format_time(*time)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_stop_id<S>(stop: &Arc<Stop>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&stop.id)
+}
+
+pub fn parse_time(s: &str) -> Result<u32, GtfsError> {
+    let invalid = |source| GtfsError::InvalidTime {
+        time: s.to_owned(),
+        source,
+    };
+    let v = s
+        .split(':')
+        .map(|part| part.parse::<u32>().map_err(|e| invalid(Some(e))))
+        .collect::<Result<Vec<_>, _>>()?;
+    match v.as_slice() {
+        [hours, minutes, seconds] => Ok(hours * 3600 + minutes * 60 + seconds),
+        _ => Err(invalid(None)),
+    }
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    parse_time(s.trim()).map_err(de::Error::custom)
+}
+
+fn deserialize_optional_time<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    match s.as_ref().map(|s| s.trim()) {
+        None | Some("") => Ok(None),
+        Some(t) => parse_time(t).map(Some).map_err(de::Error::custom),
+    }
+}
+
+fn deserialize_location_type<'de, D>(deserializer: D) -> Result<LocationType, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    Ok(match s.as_str() {
+        "1" => LocationType::StopArea,
+        "2" => LocationType::StationEntrance,
+        _ => LocationType::StopPoint,
+    })
+}
+
+/// Whether an archive member holds stop times, either `stop_times.txt` or a part like `stop_times_1.txt`
+fn is_stop_times_file(name: &str) -> bool {
+    let basename = name.rsplit('/').next().unwrap_or(name);
+    #[cfg(feature = "gzip")]
+    let basename = basename.strip_suffix(".gz").unwrap_or(basename);
+    basename.starts_with("stop_times") && basename.ends_with(".txt")
+}
+
+/// Decompresses a file compressed as `name.txt.gz`, giving its name without the `.gz` suffix
+///
+/// Compressed files are only supported with the `gzip` feature
+fn decompress<'r, T: std::io::Read + 'r>(
+    name: &str,
+    reader: T,
+) -> (&str, Box<dyn std::io::Read + 'r>) {
+    #[cfg(feature = "gzip")]
+    {
+        if let Some(name) = name.strip_suffix(".gz") {
+            return (name, Box::new(flate2::read::GzDecoder::new(reader)));
+        }
+    }
+    (name, Box::new(reader))
+}
+
+/// Path of a file of a directory, which can also be compressed as `name.gz` with the `gzip` feature
+fn find_file(dir: &Path, name: &str) -> Option<std::path::PathBuf> {
+    let path = dir.join(name);
+    if path.is_file() {
+        return Some(path);
+    }
+    #[cfg(feature = "gzip")]
+    {
+        let path = dir.join(format!("{}.gz", name));
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn seconds_to_naive_time(seconds: u32) -> Option<NaiveTime> {
+    NaiveTime::from_num_seconds_from_midnight_opt(seconds, 0)
+}
+
+/// Reader used for every GTFS file, trimming the spaces around headers and values
+fn csv_reader<T: std::io::Read>(reader: T) -> csv::Reader<T> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+}
+
+/// A GTFS file given to the `Gtfs::read_*` functions
+///
+/// Any reader is read with the default format of `csv_reader`,
+/// while `CsvInput` carries the format options of a `GtfsReader`
+trait CsvSource {
+    type Reader: std::io::Read;
+
+    fn csv_reader(self) -> CsvFile<Self::Reader>;
+}
+
+impl<T: std::io::Read> CsvSource for T {
+    type Reader = T;
+
+    fn csv_reader(self) -> CsvFile<T> {
+        CsvFile {
+            reader: csv_reader(self),
+            decimal_comma: false,
+        }
+    }
+}
+
+/// A file to read with the csv options of a `GtfsReader`
+struct CsvInput<T> {
+    reader: T,
+    delimiter: u8,
+    flexible: bool,
+    trim: bool,
+    decimal_comma: bool,
+}
+
+impl<T: std::io::Read> CsvSource for CsvInput<T> {
+    type Reader = T;
+
+    fn csv_reader(self) -> CsvFile<T> {
+        let reader = csv::ReaderBuilder::new()
+            .trim(if self.trim {
+                csv::Trim::All
+            } else {
+                csv::Trim::None
+            })
+            .delimiter(self.delimiter)
+            .flexible(self.flexible)
+            .from_reader(self.reader);
+        CsvFile {
+            reader,
+            decimal_comma: self.decimal_comma,
+        }
+    }
+}
+
+/// Csv reader giving empty trailing fields to the rows shorter than the header
+///
+/// Such rows are only accepted by a `flexible` reader, and are read one at a time like any other row.
+/// With `decimal_comma`, the commas of the coordinates of `stops.txt` are replaced by dots.
+struct CsvFile<T> {
+    reader: csv::Reader<T>,
+    decimal_comma: bool,
+}
+
+/// Columns of `stops.txt` that can be written with a decimal comma
+const COORDINATE_FIELDS: &[&str] = &["stop_lat", "stop_lon", "stop_latitude", "stop_longitude"];
+
+impl<T: std::io::Read> CsvFile<T> {
+    fn headers(&mut self) -> csv::Result<&csv::StringRecord> {
+        self.reader.headers()
+    }
+
+    fn records(&mut self) -> impl Iterator<Item = csv::Result<csv::StringRecord>> + '_ {
+        let decimal_comma = self.decimal_comma;
+        let (width, coordinates) = match self.reader.headers() {
+            Ok(headers) => (
+                headers.len(),
+                headers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, name)| decimal_comma && COORDINATE_FIELDS.contains(name))
+                    .map(|(i, _)| i)
+                    .collect(),
+            ),
+            Err(_) => (0, Vec::new()),
+        };
+        self.reader.records().map(move |record| {
+            let mut record = record?;
+            while record.len() < width {
+                record.push_field("");
+            }
+            if coordinates.is_empty() {
+                return Ok(record);
+            }
+            Ok(record
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    if coordinates.contains(&i) {
+                        field.replace(',', ".")
+                    } else {
+                        field.to_owned()
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn deserialize<'a, D: de::DeserializeOwned + 'a>(
+        &'a mut self,
+    ) -> impl Iterator<Item = csv::Result<D>> + 'a {
+        let (headers, error) = match self.reader.headers() {
+            Ok(headers) => (headers.clone(), None),
+            Err(e) => (csv::StringRecord::new(), Some(e)),
+        };
+        error.map(Err).into_iter().chain(
+            self.records()
+                .map(move |record| record?.deserialize(Some(&headers))),
+        )
+    }
+}
+
+pub fn de_with_empty_default<'de, T: Default, D>(de: D) -> Result<T, D::Error>
+where
+    D: ::serde::Deserializer<'de>,
+    T: ::serde::Deserialize<'de>,
+{
+    use serde::Deserialize;
+    Option::<T>::deserialize(de).map(|opt| opt.unwrap_or_else(Default::default))
+}
+
+fn default_location_type() -> LocationType {
+    LocationType::StopPoint
+}
+
+/// Untyped content of one GTFS file, keeping every column even those unknown to this crate
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RawFile {
+    pub headers: csv::StringRecord,
+    pub records: Vec<csv::StringRecord>,
+}
+
+impl RawFile {
+    fn from_reader<T: std::io::Read>(reader: T) -> Result<RawFile, GtfsError> {
+        let mut reader = csv_reader(reader);
+        let headers = reader.headers()?.clone();
+        let records = reader.records().collect::<Result<_, _>>()?;
+        Ok(RawFile { headers, records })
+    }
+}
+
+/// All the files of a GTFS feed as raw csv records, indexed by file name (e.g. `stops.txt`)
+///
+/// Every field is kept as a string, so it roughly doubles the memory used compared to a `Gtfs` alone
+#[derive(Debug, Default)]
+pub struct RawGtfs {
+    pub files: HashMap<String, RawFile>,
+}
+
+impl RawGtfs {
+    pub fn new(path: &str) -> Result<RawGtfs, GtfsError> {
+        let mut raw = RawGtfs::default();
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension() == Some("txt".as_ref()) {
+                let name = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| GtfsError::InvalidFileName(path.display().to_string()))?
+                    .to_owned();
+                raw.files.insert(name, RawFile::from_reader(File::open(&path)?)?);
+            }
+        }
+        Ok(raw)
+    }
+
+    pub fn get_file(&self, name: &str) -> Option<&RawFile> {
+        self.files.get(name)
+    }
+}
+
+/// How to handle a reference to an object that does not exist
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OnMissing {
+    /// Fails with a `ReferenceError`
+    Error,
+    /// Ignores the referencing object and adds a message to `Gtfs::warnings`
+    Skip,
+    /// Creates an empty object with only the id
+    Placeholder,
+}
+
+/// Options used when reading a GTFS feed
+///
+/// ```no_run
+/// let gtfs = gtfs_structures::GtfsReader::default()
+///     .error_on_unknown_route_type(true)
+///     .read("fixtures/")?;
+/// # Ok::<(), gtfs_structures::GtfsError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct GtfsReader {
+    error_on_unknown_route_type: bool,
+    error_on_duplicate_shape_points: bool,
+    error_on_invalid_color: bool,
+    read_shapes: bool,
+    read_stop_times: bool,
+    shape_lengths_only: bool,
+    summarize_shapes: bool,
+    decimal_comma: bool,
+    flexible: bool,
+    delimiter: u8,
+    trim: bool,
+    sort_stop_times: bool,
+    on_missing_stop: OnMissing,
+    require_stop_times: bool,
+    lenient: bool,
+}
+
+impl Default for GtfsReader {
+    fn default() -> GtfsReader {
+        GtfsReader {
+            error_on_unknown_route_type: false,
+            error_on_duplicate_shape_points: false,
+            error_on_invalid_color: false,
+            read_shapes: true,
+            read_stop_times: true,
+            shape_lengths_only: false,
+            summarize_shapes: false,
+            decimal_comma: false,
+            flexible: false,
+            delimiter: b',',
+            trim: true,
+            sort_stop_times: true,
+            on_missing_stop: OnMissing::Error,
+            require_stop_times: true,
+            lenient: false,
+        }
+    }
+}
+
+impl GtfsReader {
+    /// Fails instead of producing `RouteType::Other` for a `route_type` that is not known
+    pub fn error_on_unknown_route_type(mut self, value: bool) -> Self {
+        self.error_on_unknown_route_type = value;
+        self
+    }
+
+    /// Fails when points of a shape share a `shape_pt_sequence`, instead of keeping the first of them in the file
+    pub fn error_on_duplicate_shape_points(mut self, value: bool) -> Self {
+        self.error_on_duplicate_shape_points = value;
+        self
+    }
+
+    /// Fails on a `route_color` or `route_text_color` that is not six hexadecimal digits,
+    /// instead of using the default color with a warning
+    pub fn error_on_invalid_color(mut self, value: bool) -> Self {
+        self.error_on_invalid_color = value;
+        self
+    }
+
+    /// Whether `shapes.txt` is read, which is often the largest file (true by default)
+    pub fn read_shapes(mut self, value: bool) -> Self {
+        self.read_shapes = value;
+        self
+    }
+
+    /// Whether `stop_times.txt` is read (true by default)
+    ///
+    /// When disabled, the trips have no `stop_times`, which is enough to look at routes and calendars
+    pub fn read_stop_times(mut self, value: bool) -> Self {
+        self.read_stop_times = value;
+        self
+    }
+
+    /// Keeps only the length of each shape in `Gtfs::shape_lengths` and drops its points (false by default)
+    ///
+    /// The points of all the shapes are loaded before their lengths are computed once `shapes.txt` is read,
+    /// so the peak memory is not reduced, only the memory held by the returned `Gtfs`
+    pub fn shape_lengths_only(mut self, value: bool) -> Self {
+        self.shape_lengths_only = value;
+        self
+    }
+
+    /// Computes a `ShapeSummary` of each shape while reading `shapes.txt`, without keeping any point (false by default)
+    ///
+    /// Unlike `shape_lengths_only`, the memory used does not depend on the number of points,
+    /// but the points of each shape must be sorted by sequence in the file for the length to be right
+    pub fn summarize_shapes(mut self, value: bool) -> Self {
+        self.summarize_shapes = value;
+        self
+    }
+
+    /// Accepts a comma as decimal separator in the coordinates of `stops.txt`, like `"37,619"` (false by default)
+    pub fn decimal_comma(mut self, value: bool) -> Self {
+        self.decimal_comma = value;
+        self
+    }
+
+    /// Accepts rows with fewer fields than the header, the missing trailing fields being empty (false by default)
+    pub fn flexible(mut self, value: bool) -> Self {
+        self.flexible = value;
+        self
+    }
+
+    /// Separator of the fields of every file (`b','` by default)
+    pub fn delimiter(mut self, value: u8) -> Self {
+        self.delimiter = value;
+        self
+    }
+
+    /// Whether the spaces around the headers and values of every file are removed (true by default)
+    ///
+    /// When disabled, ids padded with spaces no longer match the same ids written without them in other files
+    pub fn trim(mut self, value: bool) -> Self {
+        self.trim = value;
+        self
+    }
+
+    /// Shortcut to tolerate the common defects of feeds: rows with missing trailing fields,
+    /// decimal commas in coordinates and stop times of unknown stops, which are skipped
+    /// when `on_missing_stop` is left to `OnMissing::Error` (false by default)
+    ///
+    /// It is applied when reading, on top of the other options, so `lenient(false)` undoes `lenient(true)`
+    pub fn lenient(mut self, value: bool) -> Self {
+        self.lenient = value;
+        self
+    }
+
+    /// Whether the stop times of each trip are sorted by `stop_sequence` (true by default)
+    ///
+    /// When disabled, the stop times are kept in the order of the file
+    pub fn sort_stop_times(mut self, value: bool) -> Self {
+        self.sort_stop_times = value;
+        self
+    }
+
+    /// What to do when a stop time references an unknown stop (`OnMissing::Error` by default)
+    pub fn on_missing_stop(mut self, value: OnMissing) -> Self {
+        self.on_missing_stop = value;
+        self
+    }
+
+    /// Whether a missing `stop_times.txt` is an error (true by default)
+    ///
+    /// When disabled, the trips of a feed without stop times have no `stop_times`
+    pub fn require_stop_times(mut self, value: bool) -> Self {
+        self.require_stop_times = value;
+        self
+    }
+
+    /// Reads a GTFS from a directory
+    pub fn read(&self, path: &str) -> Result<Gtfs, GtfsError> {
+        let now = Utc::now();
+        let p = Path::new(path);
+        let open = |name: &str| -> Result<(String, File), GtfsError> {
+            let path = find_file(p, name).unwrap_or_else(|| p.join(name));
+            let file = File::open(&path)?;
+            Ok((path.to_string_lossy().into_owned(), file))
+        };
+        let stops_file = open("stops.txt")?;
+        let routes_file = open("routes.txt")?;
+        let agencies_file = open("agency.txt")?;
+
+        let mut gtfs = Gtfs::default();
+
+        let (name, file) = open("trips.txt")?;
+        self.read_file(&mut gtfs, &name, file)?;
+        // Only one of calendar.txt and calendar_dates.txt is required
+        let has_calendar_dates = find_file(p, "calendar_dates.txt").is_some();
+        if find_file(p, "calendar.txt").is_some() || !has_calendar_dates {
+            let (name, file) = open("calendar.txt")?;
+            self.read_file(&mut gtfs, &name, file)?;
+        }
+        if has_calendar_dates {
+            let (name, file) = open("calendar_dates.txt")?;
+            self.read_file(&mut gtfs, &name, file)?;
+        }
+        self.read_file(&mut gtfs, &stops_file.0, stops_file.1)?;
+        self.read_file(&mut gtfs, &routes_file.0, routes_file.1)?;
+        if self.read_stop_times {
+            if find_file(p, "stop_times.txt").is_some() {
+                let (name, file) = open("stop_times.txt")?;
+                self.read_stop_times_file(&mut gtfs, decompress(&name, file).1)?;
+            } else {
+                self.missing_stop_times()?;
+            }
+        }
+        self.read_file(&mut gtfs, &agencies_file.0, agencies_file.1)?;
+        for name in &[
+            "shapes.txt",
+            "fare_attributes.txt",
+            "fare_rules.txt",
+            "fare_products.txt",
+            "fare_leg_rules.txt",
+            "frequencies.txt",
+            "transfers.txt",
+            "booking_rules.txt",
+            "location_groups.txt",
+            "location_group_stops.txt",
+        ] {
+            if find_file(p, name).is_some() {
+                let (name, file) = open(name)?;
+                self.read_file(&mut gtfs, &name, file)?;
+            }
+        }
+
+        gtfs.read_duration = Utc::now().signed_duration_since(now).num_milliseconds();
+        Ok(gtfs)
+    }
+
+    /// Reads a GTFS from a zip file
+    pub fn read_from_zip(&self, file: &str) -> Result<Gtfs, GtfsError> {
+        self.read_from_reader(File::open(file)?)
+    }
+
+    /// Reads a GTFS from a zip archive
+    pub fn read_from_reader<T: std::io::Read + std::io::Seek>(
+        &self,
+        reader: T,
+    ) -> Result<Gtfs, GtfsError> {
+        let now = Utc::now();
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut result = Gtfs::default();
+        let mut stop_times_indices = Vec::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            if is_stop_times_file(file.name()) {
+                if self.read_stop_times {
+                    stop_times_indices.push((i, file.name().to_owned()));
+                }
+            } else {
+                let name = file.name().to_owned();
+                self.read_file(&mut result, &name, file)?;
+            }
+        }
+        if stop_times_indices.is_empty() {
+            self.missing_stop_times()?;
+        }
+        for (index, name) in stop_times_indices {
+            let (_, file) = decompress(&name, archive.by_index(index)?);
+            result.read_unsorted_stop_times(self.input(file), self.missing_stop())?;
+        }
+        self.finish_stop_times(&mut result);
+
+        result.read_duration = Utc::now().signed_duration_since(now).num_milliseconds();
+        Ok(result)
+    }
+
+    /// Reads a GTFS from a tar.gz archive
+    #[cfg(feature = "targz")]
+    pub fn read_from_targz(&self, file: &str) -> Result<Gtfs, GtfsError> {
+        use std::io::Read;
+
+        let now = Utc::now();
+        let decoder = flate2::read::GzDecoder::new(File::open(file)?);
+        let mut archive = tar::Archive::new(decoder);
+        let mut result = Gtfs::default();
+        // Entries can only be read in order, so stop_times.txt is kept until trips and stops are known
+        let mut stop_times = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if is_stop_times_file(&name) {
+                if self.read_stop_times {
+                    let mut content = Vec::new();
+                    decompress(&name, entry).1.read_to_end(&mut content)?;
+                    stop_times.push(content);
+                }
+            } else {
+                self.read_file(&mut result, &name, entry)?;
+            }
+        }
+        if stop_times.is_empty() {
+            self.missing_stop_times()?;
+        }
+        for content in stop_times {
+            result.read_unsorted_stop_times(self.input(content.as_slice()), self.missing_stop())?;
+        }
+        self.finish_stop_times(&mut result);
+
+        result.read_duration = Utc::now().signed_duration_since(now).num_milliseconds();
+        Ok(result)
+    }
+
+    /// Reads an archive member according to its name; stop_times.txt must be handled by the caller
+    fn read_file<'r, T: std::io::Read + 'r>(
+        &self,
+        gtfs: &mut Gtfs,
+        name: &str,
+        file: T,
+    ) -> Result<(), GtfsError> {
+        let (name, file) = decompress(name, file);
+        let file = self.input(file);
+        if name.ends_with("calendar.txt") {
+            gtfs.read_calendars(file)?;
+        } else if name.ends_with("location_group_stops.txt") {
+            gtfs.read_location_group_stops(file)?;
+        } else if name.ends_with("location_groups.txt") {
+            gtfs.read_location_groups(file)?;
+        } else if name.ends_with("stops.txt") {
+            gtfs.read_stops(file)?;
+        } else if name.ends_with("calendar_dates.txt") {
+            gtfs.read_calendar_dates(file)?;
+        } else if name.ends_with("routes.txt") {
+            self.read_routes_file(gtfs, file)?;
+        } else if name.ends_with("trips.txt") {
+            gtfs.read_trips(file)?;
+        } else if name.ends_with("agency.txt") {
+            gtfs.read_agencies(file)?;
+        } else if name.ends_with("shapes.txt") {
+            if self.read_shapes {
+                self.read_shapes_file(gtfs, file)?;
+            }
+        } else if name.ends_with("fare_attributes.txt") {
+            gtfs.read_fare_attributes(file)?;
+        } else if name.ends_with("fare_rules.txt") {
+            gtfs.read_fare_rules(file)?;
+        } else if name.ends_with("fare_products.txt") {
+            gtfs.read_fare_products(file)?;
+        } else if name.ends_with("fare_leg_rules.txt") {
+            gtfs.read_fare_leg_rules(file)?;
+        } else if name.ends_with("frequencies.txt") {
+            gtfs.read_frequencies(file)?;
+        } else if name.ends_with("transfers.txt") {
+            gtfs.read_transfers(file)?;
+        } else if name.ends_with("booking_rules.txt") {
+            gtfs.read_booking_rules(file)?;
+        }
+        Ok(())
+    }
+
+    fn read_stop_times_file<T: std::io::Read>(
+        &self,
+        gtfs: &mut Gtfs,
+        reader: T,
+    ) -> Result<(), GtfsError> {
+        gtfs.read_unsorted_stop_times(self.input(reader), self.missing_stop())?;
+        self.finish_stop_times(gtfs);
+        Ok(())
+    }
+
+    /// A file to read with the csv options of this reader
+    fn input<T: std::io::Read>(&self, reader: T) -> CsvInput<T> {
+        CsvInput {
+            reader,
+            delimiter: self.delimiter,
+            flexible: self.flexible || self.lenient,
+            trim: self.trim,
+            decimal_comma: self.decimal_comma || self.lenient,
+        }
+    }
+
+    /// `on_missing_stop`, with the stop times of unknown stops skipped instead of an error when lenient
+    fn missing_stop(&self) -> OnMissing {
+        match self.on_missing_stop {
+            OnMissing::Error if self.lenient => OnMissing::Skip,
+            on_missing_stop => on_missing_stop,
+        }
+    }
+
+    fn read_routes_file<T: CsvSource>(&self, gtfs: &mut Gtfs, reader: T) -> Result<(), GtfsError> {
+        gtfs.read_routes_checking_colors(reader, self.error_on_invalid_color)?;
+        if self.error_on_unknown_route_type {
+            let unknown =
+                gtfs.routes_ordered()
+                    .into_iter()
+                    .find_map(|route| match route.route_type {
+                        RouteType::Other(i) if !route.route_type.is_known() => Some((route, i)),
+                        _ => None,
+                    });
+            if let Some((route, route_type)) = unknown {
+                return Err(GtfsError::InvalidRouteType {
+                    route_id: route.id.to_owned(),
+                    route_type,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn read_shapes_file<T: CsvSource>(&self, gtfs: &mut Gtfs, reader: T) -> Result<(), GtfsError> {
+        if self.summarize_shapes {
+            gtfs.read_shape_summaries(reader)
+        } else {
+            gtfs.read_shapes(reader)?;
+            self.dedup_shape_points(gtfs)?;
+            if self.shape_lengths_only {
+                GtfsReader::keep_shape_lengths(gtfs);
+            }
+            Ok(())
+        }
+    }
+
+    /// Sorts the stop times once every part of them is read, unless disabled
+    fn finish_stop_times(&self, gtfs: &mut Gtfs) {
+        if self.sort_stop_times {
+            gtfs.sort_stop_times();
+        }
+    }
+
+    fn missing_stop_times(&self) -> Result<(), GtfsError> {
+        if self.require_stop_times && self.read_stop_times {
+            Err(GtfsError::MissingFile("stop_times.txt".to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Keeps the first of the points of a shape sharing a sequence, unless it is an error
+    fn dedup_shape_points(&self, gtfs: &mut Gtfs) -> Result<(), GtfsError> {
+        let mut shape_ids: Vec<String> = gtfs.shapes.keys().cloned().collect();
+        shape_ids.sort();
+        for shape_id in shape_ids {
+            let shape = gtfs.shapes.get_mut(&shape_id).unwrap();
+            let duplicate = shape
+                .windows(2)
+                .find(|pair| pair[0].sequence == pair[1].sequence)
+                .map(|pair| pair[0].sequence);
+            if let Some(sequence) = duplicate {
+                if self.error_on_duplicate_shape_points {
+                    return Err(GtfsError::DuplicateShapePoint { shape_id, sequence });
+                }
+                shape.dedup_by_key(|point| point.sequence);
+                gtfs.warnings.push(format!(
+                    "The shape {} has several points with the same sequence, only the first one is kept",
+                    shape_id
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the points of the shapes by their length in `Gtfs::shape_lengths`
+    fn keep_shape_lengths(gtfs: &mut Gtfs) {
+        gtfs.shape_lengths = gtfs
+            .shapes
+            .keys()
+            .filter_map(|id| Some((id.to_owned(), gtfs.shape_length(id)?)))
+            .collect();
+        gtfs.shapes = HashMap::new();
+    }
+}
+
+/// A scheduled stop time once the realtime delays are applied
+#[cfg(feature = "rt")]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AdjustedStopTime {
+    pub stop_sequence: u16,
+    pub stop_id: String,
+    pub arrival_time: Option<u32>,
+    pub departure_time: Option<u32>,
+    pub arrival_delay: i64,
+    pub departure_delay: i64,
+}
+
+/// Weight of an edge of the graph built by `Gtfs::to_stop_graph`
+#[cfg(feature = "graph")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EdgeWeight {
+    /// A vehicle going from one stop to the next one of a trip, in seconds when both times are known
+    Connection { travel_time: Option<u32> },
+    /// Walking between two stops as given by `transfers.txt`
+    Transfer { min_transfer_time: Option<u32> },
+}
+
+/// How a service is defined between `calendar.txt` and `calendar_dates.txt`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ServiceKind {
+    /// Weekly pattern in `calendar.txt` with exceptions in `calendar_dates.txt`
+    RegularWithExceptions,
+    /// Weekly pattern in `calendar.txt` only
+    RegularNoExceptions,
+    /// Every day given in `calendar_dates.txt`
+    ExceptionsOnly,
+    /// The service is in neither file
+    Unknown,
+}
+
+/// Summary of the content of a feed
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GtfsStats {
+    pub read_duration: i64,
+    pub stops: usize,
+    pub routes: usize,
+    pub trips: usize,
+    pub agencies: usize,
+    pub shapes: usize,
+    pub fares: usize,
+    pub service_date_range: Option<(NaiveDate, NaiveDate)>,
+}
+
+/// A whole GTFS feed
+///
+/// The objects are kept in `HashMap`s whose iteration order changes between runs;
+/// `stops_ordered`, `routes_ordered` and `trips_ordered` give a reproducible order
+#[derive(Default, Serialize)]
+pub struct Gtfs {
+    pub read_duration: i64,
+    pub calendar: HashMap<String, Calendar>,
+    pub calendar_dates: HashMap<String, Vec<CalendarDate>>,
+    pub stops: HashMap<String, Arc<Stop>>,
+    pub routes: HashMap<String, Route>,
+    pub trips: HashMap<String, Trip>,
+    pub agencies: Vec<Agency>,
+    pub shapes: HashMap<String, Vec<Shape>>,
+    /// Length in meters of each shape, only filled when reading with `GtfsReader::shape_lengths_only`
+    pub shape_lengths: HashMap<String, f64>,
+    /// Only filled when reading with `GtfsReader::summarize_shapes`
+    pub shape_summaries: HashMap<String, ShapeSummary>,
+    pub fare_attributes: HashMap<String, FareAttribute>,
+    pub fare_rules: HashMap<String, Vec<FareRule>>,
+    pub fare_products: HashMap<String, FareProduct>,
+    pub fare_leg_rules: Vec<FareLegRule>,
+    /// Headways of the trips defined by frequency, by trip id
+    pub frequencies: HashMap<String, Vec<Frequency>>,
+    pub transfers: Vec<Transfer>,
+    pub booking_rules: HashMap<String, BookingRule>,
+    pub location_groups: HashMap<String, LocationGroup>,
+    /// Ids of the stops of each location group, by location group id
+    pub location_group_stops: HashMap<String, Vec<String>>,
+    pub warnings: Vec<String>,
+}
+
+impl Gtfs {
+    pub fn stats(&self) -> GtfsStats {
+        GtfsStats {
+            read_duration: self.read_duration,
+            stops: self.stops.len(),
+            routes: self.routes.len(),
+            trips: self.trips.len(),
+            agencies: self.agencies.len(),
+            shapes: self.shapes.len() + self.shape_lengths.len() + self.shape_summaries.len(),
+            fares: self.fare_attributes.len(),
+            service_date_range: self.service_date_range(),
+        }
+    }
+
+    pub fn print_stats(&self) {
+        let stats = self.stats();
+        println!("GTFS data:");
+        println!("  Read in {} ms", stats.read_duration);
+        println!("  Stops: {}", stats.stops);
+        println!("  Routes: {}", stats.routes);
+        println!("  Trips: {}", stats.trips);
+        println!("  Agencies: {}", stats.agencies);
+        println!("  Shapes: {}", stats.shapes);
+        println!("  Fares: {}", stats.fares);
+        if let Some((start, end)) = stats.service_date_range {
+            println!("  Service: {}—{}", start, end);
+        }
+    }
+
+    /// Whether both feeds have the same content, whatever the order of their objects
+    ///
+    /// The stop times of a trip and the points of a shape may be in any order;
+    /// `read_duration` and `warnings` are ignored
+    pub fn content_eq(&self, other: &Gtfs) -> bool {
+        self.calendar == other.calendar
+            && map_content_eq(&self.calendar_dates, &other.calendar_dates, |a, b| {
+                same_elements(a, b)
+            })
+            && self.stops == other.stops
+            && self.routes == other.routes
+            && map_content_eq(&self.trips, &other.trips, trip_content_eq)
+            && same_elements(&self.agencies, &other.agencies)
+            && map_content_eq(&self.shapes, &other.shapes, |a, b| {
+                sorted_by_key(a, |point| point.sequence) == sorted_by_key(b, |point| point.sequence)
+            })
+            && self.shape_lengths == other.shape_lengths
+            && self.shape_summaries == other.shape_summaries
+            && self.fare_attributes == other.fare_attributes
+            && map_content_eq(&self.fare_rules, &other.fare_rules, |a, b| {
+                same_elements(a, b)
+            })
+            && self.fare_products == other.fare_products
+            && same_elements(&self.fare_leg_rules, &other.fare_leg_rules)
+            && map_content_eq(&self.frequencies, &other.frequencies, |a, b| {
+                same_elements(a, b)
+            })
+            && same_elements(&self.transfers, &other.transfers)
+            && self.booking_rules == other.booking_rules
+            && self.location_groups == other.location_groups
+            && map_content_eq(
+                &self.location_group_stops,
+                &other.location_group_stops,
+                |a, b| same_elements(a, b),
+            )
+    }
+
+    /// Hash of the content of the feed, to detect whether a new version of a feed actually changed
+    ///
+    /// Like `content_eq`, it does not depend on the order of the objects, and it is stable across runs
+    pub fn content_hash(&self) -> u64 {
+        let sections = vec![
+            sorted_hashes(&self.calendar),
+            sorted_hashes(self.calendar_dates.values().flatten()),
+            sorted_hashes(&self.stops),
+            sorted_hashes(&self.routes),
+            {
+                let mut trips: Vec<u64> = self.trips.values().map(trip_hash).collect();
+                trips.sort_unstable();
+                trips
+            },
+            sorted_hashes(&self.agencies),
+            sorted_hashes(self.shapes.values().flatten()),
+            sorted_hashes(&self.shape_lengths),
+            sorted_hashes(&self.shape_summaries),
+            sorted_hashes(&self.fare_attributes),
+            sorted_hashes(self.fare_rules.values().flatten()),
+            sorted_hashes(&self.fare_products),
+            sorted_hashes(&self.fare_leg_rules),
+            sorted_hashes(self.frequencies.values().flatten()),
+            sorted_hashes(&self.transfers),
+            sorted_hashes(&self.booking_rules),
+            sorted_hashes(&self.location_groups),
+            sorted_hashes(
+                self.location_group_stops
+                    .iter()
+                    .flat_map(|(group_id, stop_ids)| stop_ids.iter().map(move |id| (group_id, id))),
+            ),
+        ];
+        let mut hasher = Fnv1a::default();
+        for section in sections {
+            hasher.write_u64(section.len() as u64);
+            for item in section {
+                hasher.write_u64(item);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Prepends `prefix` to every id and to every reference to an id, to merge feeds without collision
+    ///
+    /// Stops, routes, trips, services, shapes, blocks, fares, zones, agencies, booking rules and location groups are prefixed
+    pub fn add_prefix(&mut self, prefix: &str) {
+        let p = |id: &str| format!("{}{}", prefix, id);
+        let p_opt = |id: &mut Option<String>| {
+            if let Some(id) = id {
+                *id = p(id);
+            }
+        };
+
+        self.calendar = prefixed_keys(std::mem::take(&mut self.calendar), prefix);
+        for calendar in self.calendar.values_mut() {
+            calendar.id = p(&calendar.id);
+        }
+        self.calendar_dates = prefixed_keys(std::mem::take(&mut self.calendar_dates), prefix);
+        for date in self.calendar_dates.values_mut().flatten() {
+            date.service_id = p(&date.service_id);
+        }
+
+        self.stops = std::mem::take(&mut self.stops)
+            .into_values()
+            .map(|stop| {
+                let mut stop = Stop::clone(&stop);
+                stop.id = p(&stop.id);
+                p_opt(&mut stop.parent_station);
+                p_opt(&mut stop.zone_id);
+                (stop.id.to_owned(), Arc::new(stop))
+            })
+            .collect();
+
+        self.routes = prefixed_keys(std::mem::take(&mut self.routes), prefix);
+        for route in self.routes.values_mut() {
+            route.id = p(&route.id);
+            p_opt(&mut route.agency_id);
+            p_opt(&mut route.network_id);
+        }
+
+        self.trips = prefixed_keys(std::mem::take(&mut self.trips), prefix);
+        for trip in self.trips.values_mut() {
+            trip.id = p(&trip.id);
+            trip.route_id = p(&trip.route_id);
+            trip.service_id = p(&trip.service_id);
+            p_opt(&mut trip.shape_id);
+            p_opt(&mut trip.block_id);
+            for stop_time in &mut trip.stop_times {
+                let id = p(&stop_time.stop.id);
+                stop_time.stop = match self.stops.get(&id) {
+                    Some(stop) => Arc::clone(stop),
+                    None => Arc::new(Stop {
+                        id,
+                        ..Stop::clone(&stop_time.stop)
+                    }),
+                };
+            }
+        }
+
+        for agency in &mut self.agencies {
+            p_opt(&mut agency.id);
+        }
+
+        self.shapes = prefixed_keys(std::mem::take(&mut self.shapes), prefix);
+        for point in self.shapes.values_mut().flatten() {
+            point.id = p(&point.id);
+        }
+        self.shape_lengths = prefixed_keys(std::mem::take(&mut self.shape_lengths), prefix);
+        self.shape_summaries = prefixed_keys(std::mem::take(&mut self.shape_summaries), prefix);
+
+        self.fare_attributes = prefixed_keys(std::mem::take(&mut self.fare_attributes), prefix);
+        for fare in self.fare_attributes.values_mut() {
+            fare.id = p(&fare.id);
+            p_opt(&mut fare.agency_id);
+        }
+        self.fare_rules = prefixed_keys(std::mem::take(&mut self.fare_rules), prefix);
+        for rule in self.fare_rules.values_mut().flatten() {
+            rule.fare_id = p(&rule.fare_id);
+            p_opt(&mut rule.route_id);
+            p_opt(&mut rule.origin_id);
+            p_opt(&mut rule.destination_id);
+            p_opt(&mut rule.contains_id);
+        }
+
+        self.fare_products = prefixed_keys(std::mem::take(&mut self.fare_products), prefix);
+        for product in self.fare_products.values_mut() {
+            product.id = p(&product.id);
+            p_opt(&mut product.fare_media_id);
+        }
+        for rule in &mut self.fare_leg_rules {
+            rule.fare_product_id = p(&rule.fare_product_id);
+            p_opt(&mut rule.leg_group_id);
+            p_opt(&mut rule.network_id);
+            p_opt(&mut rule.from_area_id);
+            p_opt(&mut rule.to_area_id);
+        }
+
+        self.frequencies = prefixed_keys(std::mem::take(&mut self.frequencies), prefix);
+        for frequency in self.frequencies.values_mut().flatten() {
+            frequency.trip_id = p(&frequency.trip_id);
+        }
+
+        for transfer in &mut self.transfers {
+            p_opt(&mut transfer.from_stop_id);
+            p_opt(&mut transfer.to_stop_id);
+            p_opt(&mut transfer.from_route_id);
+            p_opt(&mut transfer.to_route_id);
+            p_opt(&mut transfer.from_trip_id);
+            p_opt(&mut transfer.to_trip_id);
+        }
+
+        self.booking_rules = prefixed_keys(std::mem::take(&mut self.booking_rules), prefix);
+        for rule in self.booking_rules.values_mut() {
+            rule.id = p(&rule.id);
+            p_opt(&mut rule.prior_notice_service_id);
+        }
+
+        self.location_groups = prefixed_keys(std::mem::take(&mut self.location_groups), prefix);
+        for group in self.location_groups.values_mut() {
+            group.id = p(&group.id);
+        }
+        self.location_group_stops =
+            prefixed_keys(std::mem::take(&mut self.location_group_stops), prefix);
+        for stop_id in self.location_group_stops.values_mut().flatten() {
+            *stop_id = p(stop_id);
+        }
+    }
+
+    pub fn new(path: &str) -> Result<Gtfs, GtfsError> {
+        Gtfs::check_required_files(path)?;
+        GtfsReader::default().read(path)
+    }
+
+    /// Builds a feed from objects created in code, linking the stop times to their trip and stop
+    ///
+    /// The other objects, like shapes or fares, can be set afterwards through the public fields
+    pub fn from_parts(
+        stops: Vec<Stop>,
+        routes: Vec<Route>,
+        trips: Vec<Trip>,
+        calendar: Vec<Calendar>,
+        calendar_dates: Vec<CalendarDate>,
+        agencies: Vec<Agency>,
+        stop_times: Vec<StopTimeRow>,
+    ) -> Result<Gtfs, GtfsError> {
+        let mut gtfs = Gtfs {
+            stops: stops
+                .into_iter()
+                .map(|stop| (stop.id.to_owned(), Arc::new(stop)))
+                .collect(),
+            routes: routes
+                .into_iter()
+                .map(|route| (route.id.to_owned(), route))
+                .collect(),
+            trips: trips
+                .into_iter()
+                .map(|trip| (trip.id.to_owned(), trip))
+                .collect(),
+            calendar: calendar
+                .into_iter()
+                .map(|calendar| (calendar.id.to_owned(), calendar))
+                .collect(),
+            agencies,
+            ..Gtfs::default()
+        };
+        for date in calendar_dates {
+            gtfs.calendar_dates
+                .entry(date.service_id.to_owned())
+                .or_default()
+                .push(date);
+        }
+        for stop_time in stop_times {
+            gtfs.add_stop_time(stop_time, OnMissing::Error)?;
+        }
+        gtfs.sort_stop_times();
+        Ok(gtfs)
+    }
+
+    /// Checks that a directory has every mandatory file, listing all the missing ones in the error
+    pub fn check_required_files(path: &str) -> Result<(), GtfsError> {
         let p = Path::new(path);
-        let calendar_file = File::open(p.join("calendar.txt"))?;
-        let stops_file = File::open(p.join("stops.txt"))?;
-        let calendar_dates_file = File::open(p.join("calendar_dates.txt"))?;
-        let routes_file = File::open(p.join("routes.txt"))?;
-        let stop_times_file = File::open(p.join("stop_times.txt"))?;
-        let agencies_file = File::open(p.join("agency.txt"))?;
+        let mut missing: Vec<String> = [
+            "agency.txt",
+            "stops.txt",
+            "routes.txt",
+            "trips.txt",
+            "stop_times.txt",
+        ]
+        .iter()
+        .filter(|name| find_file(p, name).is_none())
+        .map(|name| name.to_string())
+        .collect();
+        if find_file(p, "calendar.txt").is_none() && find_file(p, "calendar_dates.txt").is_none() {
+            missing.push("calendar.txt or calendar_dates.txt".to_owned());
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(GtfsError::MissingFiles(missing))
+        }
+    }
+
+    /// Reads a GTFS directory without loading `shapes.txt`, which is often the largest file
+    pub fn new_without_shapes(path: &str) -> Result<Gtfs, GtfsError> {
+        GtfsReader::default().read_shapes(false).read(path)
+    }
+
+    pub fn from_path_preserving(path: &str) -> Result<(Gtfs, RawGtfs), GtfsError> {
+        Ok((Gtfs::new(path)?, RawGtfs::new(path)?))
+    }
+
+    /// Serializes the whole feed as a JSON document
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<String, GtfsError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_zip(file: &str) -> Result<Gtfs, GtfsError> {
+        GtfsReader::default().read_from_zip(file)
+    }
+
+    #[cfg(feature = "read-url")]
+    pub fn from_url(url: &str) -> Result<Gtfs, GtfsError> {
+        let mut res = reqwest::get(url)?;
+        let mut body = Vec::new();
+        res.read_to_end(&mut body)?;
+        let cursor = std::io::Cursor::new(body);
+        Gtfs::from_reader(cursor)
+    }
+
+    pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: T) -> Result<Gtfs, GtfsError> {
+        GtfsReader::default().read_from_reader(reader)
+    }
+
+    /// Reads a zipped GTFS already loaded in memory
+    pub fn from_bytes(data: &[u8]) -> Result<Gtfs, GtfsError> {
+        Gtfs::from_reader(std::io::Cursor::new(data))
+    }
+
+    #[cfg(feature = "targz")]
+    pub fn from_targz(file: &str) -> Result<Gtfs, GtfsError> {
+        GtfsReader::default().read_from_targz(file)
+    }
+
+    fn read_calendars<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.calendar = reader
+            .deserialize()
+            .map(|res| res.map(|e: Calendar| (e.id.to_owned(), e)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(())
+    }
+
+    fn read_calendar_dates<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        for result in reader.deserialize() {
+            let record: CalendarDate = result?;
+            let calendar_date = self
+                .calendar_dates
+                .entry(record.service_id.to_owned())
+                .or_insert_with(Vec::new);
+            calendar_date.push(record);
+        }
+        Ok(())
+    }
+
+    fn read_stops<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.stops = reader
+            .deserialize()
+            .map(|res| res.map(|e: Stop| (e.id.to_owned(), Arc::new(e))))
+            .collect::<Result<_, _>>()?;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn read_routes<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        self.read_routes_checking_colors(reader, false)
+    }
+
+    /// Reads the routes, an invalid color being replaced by the default one with a warning unless `error_on_invalid_color` is set
+    fn read_routes_checking_colors<T: CsvSource>(
+        &mut self,
+        reader: T,
+        error_on_invalid_color: bool,
+    ) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        let headers = reader.headers()?.clone();
+        let color_columns: Vec<(usize, &str)> = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| ["route_color", "route_text_color"].contains(name))
+            .collect();
+        self.routes = HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            let route: Route = record.deserialize(Some(&headers))?;
+            for &(i, column) in &color_columns {
+                let value = &record[i];
+                if !value.is_empty() && value.parse::<Color>().is_err() {
+                    if error_on_invalid_color {
+                        return Err(GtfsError::InvalidColor(value.to_owned()));
+                    }
+                    self.warnings.push(format!(
+                        "Invalid {} {} for the route {}, the default color is used",
+                        column, value, route.id
+                    ));
+                }
+            }
+            self.routes.insert(route.id.to_owned(), route);
+        }
+        Ok(())
+    }
+
+    fn read_trips<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.trips = reader
+            .deserialize()
+            .map(|res| res.map(|e: Trip| (e.id.to_owned(), e)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(())
+    }
+
+    fn read_agencies<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        let headers = reader.headers()?.clone();
+        // The unknown columns are copied from the raw record, so their values are never reinterpreted
+        let extra_columns: Vec<(usize, &str)> = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| !AGENCY_FIELDS.contains(header))
+            .collect();
+        self.agencies = reader
+            .records()
+            .map(|record| {
+                let record = record?;
+                let mut agency: Agency = record.deserialize(Some(&headers))?;
+                agency.extra = extra_columns
+                    .iter()
+                    .map(|&(i, header)| (header.to_owned(), record[i].to_owned()))
+                    .collect();
+                Ok(agency)
+            })
+            .collect::<Result<_, GtfsError>>()?;
+
+        Ok(())
+    }
+
+    fn read_shapes<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        for result in reader.deserialize() {
+            let record: Shape = result?;
+            self.shapes
+                .entry(record.id.to_owned())
+                .or_default()
+                .push(record);
+        }
+        // The sort is stable, so points with the same sequence stay in the order of the file
+        for shape in self.shapes.values_mut() {
+            shape.sort_by_key(|point| point.sequence);
+        }
+        Ok(())
+    }
+
+    fn read_shape_summaries<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut unordered = HashSet::new();
+        for result in reader.csv_reader().deserialize() {
+            let point: Shape = result?;
+            if !self
+                .shape_summaries
+                .entry(point.id.to_owned())
+                .or_default()
+                .add(&point)
+            {
+                unordered.insert(point.id);
+            }
+        }
+        let mut unordered: Vec<String> = unordered.into_iter().collect();
+        unordered.sort();
+        for id in unordered {
+            self.warnings.push(format!(
+                "The points of the shape {} are not sorted by sequence, its length is approximate",
+                id
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_fare_attributes<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.fare_attributes = reader
+            .deserialize()
+            .map(|res| res.map(|e: FareAttribute| (e.id.to_owned(), e)))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn read_fare_products<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.fare_products = reader
+            .deserialize()
+            .map(|res| res.map(|e: FareProduct| (e.id.to_owned(), e)))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn read_fare_leg_rules<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.fare_leg_rules = reader.deserialize().collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn read_booking_rules<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.booking_rules = reader
+            .deserialize()
+            .map(|res| res.map(|e: BookingRule| (e.id.to_owned(), e)))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn read_transfers<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.transfers = reader.deserialize().collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn read_fare_rules<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        for result in reader.deserialize() {
+            let record: FareRule = result?;
+            self.fare_rules
+                .entry(record.fare_id.to_owned())
+                .or_default()
+                .push(record);
+        }
+        Ok(())
+    }
+
+    fn read_location_groups<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        self.location_groups = reader
+            .deserialize()
+            .map(|res| res.map(|e: LocationGroup| (e.id.to_owned(), e)))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn read_location_group_stops<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        for result in reader.deserialize() {
+            let record: LocationGroupStop = result?;
+            self.location_group_stops
+                .entry(record.location_group_id)
+                .or_default()
+                .push(record.stop_id);
+        }
+        Ok(())
+    }
+
+    fn read_frequencies<T: CsvSource>(&mut self, reader: T) -> Result<(), GtfsError> {
+        let mut reader = reader.csv_reader();
+        for result in reader.deserialize() {
+            let record: Frequency = result?;
+            self.frequencies
+                .entry(record.trip_id.to_owned())
+                .or_default()
+                .push(record);
+        }
+        Ok(())
+    }
+
+    /// Reads the stop times with the default options of `GtfsReader`
+    #[cfg(test)]
+    fn read_stop_times<T: std::io::Read>(&mut self, reader: T) -> Result<(), GtfsError> {
+        GtfsReader::default().read_stop_times_file(self, reader)
+    }
+
+    fn read_unsorted_stop_times<T: CsvSource>(
+        &mut self,
+        reader: T,
+        on_missing_stop: OnMissing,
+    ) -> Result<(), GtfsError> {
+        let trips_loaded = !self.trips.is_empty();
+        for stop_time in reader.csv_reader().deserialize() {
+            let s: StopTimeRow = stop_time?;
+            if !trips_loaded {
+                return Err(StopTimeError::TripNotLoaded { trip_id: s.trip_id }.into());
+            }
+            self.add_stop_time(s, on_missing_stop)?;
+        }
+        Ok(())
+    }
+
+    /// Links a stop time to its stop and pushes it at the end of the stop times of its trip
+    fn add_stop_time(
+        &mut self,
+        s: StopTimeRow,
+        on_missing_stop: OnMissing,
+    ) -> Result<(), GtfsError> {
+        let trip = &mut self.trips.get_mut(&s.trip_id).ok_or(ReferenceError {
+            id: s.trip_id.to_string(),
+        })?;
+        let stop = match (self.stops.get(&s.stop_id), on_missing_stop) {
+            (Some(stop), _) => Arc::clone(stop),
+            (None, OnMissing::Error) => {
+                return Err(ReferenceError {
+                    id: s.stop_id.to_string(),
+                }
+                .into())
+            }
+            (None, OnMissing::Skip) => {
+                self.warnings.push(format!(
+                    "Skipped a stop time of the trip {}: unknown stop {}",
+                    s.trip_id, s.stop_id
+                ));
+                return Ok(());
+            }
+            (None, OnMissing::Placeholder) => {
+                let placeholder = Arc::new(Stop {
+                    id: s.stop_id.to_owned(),
+                    ..Stop::default()
+                });
+                self.stops
+                    .insert(s.stop_id.to_owned(), Arc::clone(&placeholder));
+                placeholder
+            }
+        };
+        trip.stop_times.push(StopTime::from(&s, stop));
+        Ok(())
+    }
+
+    /// Calls `f` for each line of a `stop_times.txt` without loading them in trips
+    ///
+    /// Useful to aggregate the stop times of feeds too large to be held in memory
+    pub fn for_each_stop_time<T, F>(reader: T, mut f: F) -> Result<(), GtfsError>
+    where
+        T: std::io::Read,
+        F: FnMut(StopTimeRow),
+    {
+        for row in csv_reader(reader).deserialize() {
+            f(row?);
+        }
+        Ok(())
+    }
+
+    fn sort_stop_times(&mut self) {
+        for trip in &mut self.trips.values_mut() {
+            trip.stop_times
+                .sort_by(|a, b| a.stop_sequence.cmp(&b.stop_sequence))
+        }
+    }
+
+    /// Fills the missing arrival and departure times of every trip
+    ///
+    /// Times are linearly interpolated between the surrounding known times,
+    /// proportionally to `shape_dist_traveled` when available and increasing, or to the number of stops otherwise
+    pub fn interpolate_stop_times(&mut self) {
+        for trip in self.trips.values_mut() {
+            interpolate_trip_stop_times(&mut trip.stop_times);
+        }
+    }
+
+    /// Returns the (trip_id, stop_sequence) of the stop times reached before the previous stop was left
+    pub fn validate_stop_time_ordering(&self) -> Vec<(String, u16)> {
+        let mut errors: Vec<(String, u16)> = self
+            .trips
+            .values()
+            .flat_map(|trip| {
+                trip.stop_times
+                    .windows(2)
+                    .filter(|pair| {
+                        let previous = pair[0].departure_time.or(pair[0].arrival_time);
+                        let current = pair[1].arrival_time.or(pair[1].departure_time);
+                        match (previous, current) {
+                            (Some(previous), Some(current)) => current < previous,
+                            _ => false,
+                        }
+                    })
+                    .map(move |pair| (trip.id.to_owned(), pair[1].stop_sequence))
+            })
+            .collect();
+        errors.sort();
+        errors
+    }
+
+    pub fn trip_days(&self, service_id: &str, start_date: NaiveDate) -> Vec<u16> {
+        let mut result = Vec::new();
+
+        // Handle services given by specific days and exceptions
+        let mut removed_days = HashSet::new();
+        for extra_day in self
+            .calendar_dates
+            .get(service_id)
+            .iter()
+            .flat_map(|e| e.iter())
+        {
+            let offset = extra_day.date.signed_duration_since(start_date).num_days();
+            if offset >= 0 {
+                if extra_day.exception_type == 1 {
+                    result.push(offset as u16);
+                } else if extra_day.exception_type == 2 {
+                    removed_days.insert(offset);
+                }
+            }
+        }
+
+        if let Some(calendar) = self.calendar.get(service_id) {
+            let total_days = calendar
+                .end_date
+                .signed_duration_since(start_date)
+                .num_days();
+            for days_offset in 0..=total_days {
+                let current_date = start_date + Duration::days(days_offset);
+
+                if calendar.start_date <= current_date
+                    && calendar.end_date >= current_date
+                    && calendar.valid_weekday(current_date)
+                    && !removed_days.contains(&days_offset)
+                {
+                    result.push(days_offset as u16);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Whether a service is defined by `calendar.txt`, `calendar_dates.txt` or both
+    pub fn service_type(&self, service_id: &str) -> ServiceKind {
+        let has_dates = self
+            .calendar_dates
+            .get(service_id)
+            .map_or(false, |dates| !dates.is_empty());
+        match (self.calendar.contains_key(service_id), has_dates) {
+            (true, true) => ServiceKind::RegularWithExceptions,
+            (true, false) => ServiceKind::RegularNoExceptions,
+            (false, true) => ServiceKind::ExceptionsOnly,
+            (false, false) => ServiceKind::Unknown,
+        }
+    }
+
+    /// Removes the trips whose service runs on no date between `start` and `end`, both included
+    ///
+    /// The objects only used by the removed trips are kept; see `remove_unreferenced` to drop them.
+    /// Nothing is removed when `start` is after `end`, as such a range is most likely a mistake
+    pub fn filter_to_date_range(&mut self, start: NaiveDate, end: NaiveDate) {
+        if start > end {
+            return;
+        }
+        let service_ids: HashSet<&str> = self
+            .trips
+            .values()
+            .map(|trip| trip.service_id.as_str())
+            .collect();
+        let active: HashSet<String> = service_ids
+            .into_iter()
+            .filter(|service_id| {
+                start
+                    .iter_days()
+                    .take_while(|date| *date <= end)
+                    .any(|date| self.is_service_active(service_id, date))
+            })
+            .map(|service_id| service_id.to_owned())
+            .collect();
+        self.trips
+            .retain(|_, trip| active.contains(&trip.service_id));
+        let trips = &self.trips;
+        self.frequencies
+            .retain(|trip_id, _| trips.contains_key(trip_id));
+    }
+
+    /// Removes the services, shapes, stops and routes that no trip references
+    ///
+    /// Stations are kept as long as one of their children is used.
+    /// The agencies are all kept, as they give the timezone of the feed
+    pub fn remove_unreferenced(&mut self) {
+        let unused_stops: Vec<String> = self
+            .unused_stops()
+            .iter()
+            .map(|stop| stop.id.to_owned())
+            .collect();
+        for stop_id in unused_stops {
+            self.stops.remove(&stop_id);
+        }
+        let service_ids: HashSet<String> = self
+            .trips
+            .values()
+            .map(|trip| trip.service_id.to_owned())
+            .collect();
+        self.calendar.retain(|id, _| service_ids.contains(id));
+        self.calendar_dates.retain(|id, _| service_ids.contains(id));
+        let shape_ids: HashSet<String> = self
+            .trips
+            .values()
+            .filter_map(|trip| trip.shape_id.to_owned())
+            .collect();
+        self.shapes.retain(|id, _| shape_ids.contains(id));
+        self.shape_lengths.retain(|id, _| shape_ids.contains(id));
+        self.shape_summaries.retain(|id, _| shape_ids.contains(id));
+        let route_ids: HashSet<String> = self
+            .trips
+            .values()
+            .map(|trip| trip.route_id.to_owned())
+            .collect();
+        self.routes.retain(|id, _| route_ids.contains(id));
+    }
+
+    /// Whether a service runs on a date, according to its calendar and calendar dates
+    pub fn is_service_active(&self, service_id: &str, date: NaiveDate) -> bool {
+        if let Some(exception) = self
+            .calendar_dates
+            .get(service_id)
+            .and_then(|dates| dates.iter().find(|d| d.date == date))
+        {
+            return exception.exception_type == 1;
+        }
+        self.calendar.get(service_id).map_or(false, |calendar| {
+            calendar.start_date <= date && date <= calendar.end_date && calendar.valid_weekday(date)
+        })
+    }
+
+    /// Ids of the calendars running on a weekday, sorted
+    ///
+    /// The exceptions of `calendar_dates.txt` and the validity period of the calendars are ignored
+    pub fn services_on_weekday(&self, weekday: Weekday) -> Vec<&str> {
+        let mut ids: Vec<&str> = self
+            .calendar
+            .values()
+            .filter(|calendar| calendar.runs_on(weekday))
+            .map(|calendar| calendar.id.as_str())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Trips running on a date that can accommodate a wheelchair, sorted by id
+    pub fn accessible_trips_on_date(&self, date: NaiveDate) -> Vec<&Trip> {
+        let mut trips: Vec<&Trip> = self
+            .trips
+            .values()
+            .filter(|trip| trip.wheelchair_accessible == Availability::Available)
+            .filter(|trip| self.is_service_active(&trip.service_id, date))
+            .collect();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+        trips
+    }
+
+    /// Stop times at a stop of the trips running on a date, sorted by departure time
+    pub fn departures_from(&self, stop_id: &str, date: NaiveDate) -> Vec<(&Trip, &StopTime)> {
+        let mut departures: Vec<(&Trip, &StopTime)> = self
+            .trips
+            .values()
+            .filter(|trip| self.is_service_active(&trip.service_id, date))
+            .flat_map(|trip| {
+                trip.stop_times
+                    .iter()
+                    .filter(|st| st.stop.id == stop_id)
+                    .map(move |st| (trip, st))
+            })
+            .collect();
+        departures.sort_by_key(|(trip, st)| match st.departure_time.or(st.arrival_time) {
+            Some(time) => (false, time, trip.id.as_str()),
+            None => (true, 0, trip.id.as_str()),
+        });
+        departures
+    }
+
+    /// Seconds between consecutive departures of a route from a stop on a date
+    pub fn headways_at_stop(&self, stop_id: &str, route_id: &str, date: NaiveDate) -> Vec<u32> {
+        let times: Vec<u32> = self
+            .departures_from(stop_id, date)
+            .iter()
+            .filter(|(trip, _)| trip.route_id == route_id)
+            .filter_map(|(_, st)| st.departure_time.or(st.arrival_time))
+            .collect();
+        times.windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+
+    /// Up to `limit` departures from a stop on a date at or after `after` seconds since midnight, sorted by time
+    ///
+    /// Times are counted from midnight of `date`, so its trips running past midnight come last; trips of the
+    /// previous day running past midnight are included when their service runs that previous day
+    pub fn next_departures(
+        &self,
+        stop_id: &str,
+        date: NaiveDate,
+        after: u32,
+        limit: usize,
+    ) -> Vec<(&Trip, &StopTime)> {
+        const DAY: u32 = 24 * 3600;
+        let mut service_days = vec![(date, 0)];
+        if let Some(previous_day) = date.pred_opt() {
+            service_days.push((previous_day, DAY));
+        }
+        let mut departures: Vec<(u32, &Trip, &StopTime)> = Vec::new();
+        for (service_date, offset) in service_days {
+            for trip in self
+                .trips
+                .values()
+                .filter(|trip| self.is_service_active(&trip.service_id, service_date))
+            {
+                for st in trip.stop_times.iter().filter(|st| st.stop.id == stop_id) {
+                    match st.departure_time.or(st.arrival_time) {
+                        Some(time) if time >= offset && time - offset >= after => {
+                            departures.push((time - offset, trip, st))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        departures.sort_by(|a, b| (a.0, &a.1.id).cmp(&(b.0, &b.1.id)));
+        departures
+            .into_iter()
+            .take(limit)
+            .map(|(_, trip, st)| (trip, st))
+            .collect()
+    }
+
+    /// Number of trips of a route starting in each hour of a date
+    ///
+    /// Times after midnight of the next day are counted in their hour modulo 24
+    pub fn route_hourly_frequency(&self, route_id: &str, date: NaiveDate) -> [u32; 24] {
+        let mut frequency = [0; 24];
+        for trip in self.trips.values().filter(|trip| {
+            trip.route_id == route_id && self.is_service_active(&trip.service_id, date)
+        }) {
+            if let Some(start) = trip.start_time() {
+                frequency[(start / 3600 % 24) as usize] += 1;
+            }
+        }
+        frequency
+    }
+
+    pub fn service_date_range(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let calendar_bounds = self
+            .calendar
+            .values()
+            .flat_map(|c| vec![c.start_date, c.end_date]);
+        let calendar_dates = self
+            .calendar_dates
+            .values()
+            .flat_map(|dates| dates.iter().map(|d| d.date));
+        calendar_bounds
+            .chain(calendar_dates)
+            .fold(None, |range, date| match range {
+                None => Some((date, date)),
+                Some((min, max)) => Some((min.min(date), max.max(date))),
+            })
+    }
+
+    /// Calendar dates of every service between `start` and `end` included, sorted by date and service
+    pub fn calendar_exceptions_in_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Vec<&CalendarDate> {
+        let mut dates: Vec<&CalendarDate> = self
+            .calendar_dates
+            .values()
+            .flatten()
+            .filter(|d| start <= d.date && d.date <= end)
+            .collect();
+        dates.sort_by(|a, b| (a.date, &a.service_id).cmp(&(b.date, &b.service_id)));
+        dates
+    }
+
+    pub fn stop_ids(&self) -> Vec<&str> {
+        self.stops.keys().map(|id| id.as_str()).collect()
+    }
+
+    pub fn route_ids(&self) -> Vec<&str> {
+        self.routes.keys().map(|id| id.as_str()).collect()
+    }
+
+    pub fn trip_ids(&self) -> Vec<&str> {
+        self.trips.keys().map(|id| id.as_str()).collect()
+    }
+
+    pub fn shape_ids(&self) -> Vec<&str> {
+        self.shapes.keys().map(|id| id.as_str()).collect()
+    }
+
+    /// Stops that no stop time references, sorted by id
+    ///
+    /// A station is only listed if none of its children is used either
+    pub fn unused_stops(&self) -> Vec<&Arc<Stop>> {
+        let used: HashSet<&str> = self
+            .trips
+            .values()
+            .flat_map(|trip| trip.stop_times.iter().map(|st| st.stop.id.as_str()))
+            .collect();
+        let used_parents: HashSet<&str> = used
+            .iter()
+            .filter_map(|id| self.stops.get(*id))
+            .filter_map(|stop| stop.parent_station.as_deref())
+            .collect();
+        let mut stops: Vec<&Arc<Stop>> = self
+            .stops
+            .values()
+            .filter(|stop| {
+                !used.contains(stop.id.as_str()) && !used_parents.contains(stop.id.as_str())
+            })
+            .collect();
+        stops.sort_by(|a, b| a.id.cmp(&b.id));
+        stops
+    }
+
+    /// Area covered by the feed, as (min_lat, min_lon, max_lat, max_lon)
+    ///
+    /// Stops without coordinates are ignored. Shape points are included when `include_shapes` is set
+    pub fn bounding_box(&self, include_shapes: bool) -> Option<(f64, f64, f64, f64)> {
+        let mut points: Vec<(f64, f64)> = self
+            .stops
+            .values()
+            .map(|stop| (stop.latitude, stop.longitude))
+            .collect();
+        if include_shapes {
+            points.extend(
+                self.shapes
+                    .values()
+                    .flatten()
+                    .map(|point| (point.latitude, point.longitude)),
+            );
+            for summary in self.shape_summaries.values().filter(|s| s.point_count > 0) {
+                points.push((summary.min_latitude, summary.min_longitude));
+                points.push((summary.max_latitude, summary.max_longitude));
+            }
+        }
+        points
+            .into_iter()
+            .filter(|&(lat, lon)| lat != 0.0 || lon != 0.0)
+            .fold(None, |bbox, (lat, lon)| match bbox {
+                None => Some((lat, lon, lat, lon)),
+                Some((min_lat, min_lon, max_lat, max_lon)) => Some((
+                    f64::min(min_lat, lat),
+                    f64::min(min_lon, lon),
+                    f64::max(max_lat, lat),
+                    f64::max(max_lon, lon),
+                )),
+            })
+    }
+
+    /// Stops sorted by id
+    pub fn stops_ordered(&self) -> Vec<&Stop> {
+        let mut stops: Vec<&Stop> = self.stops.values().map(|stop| stop.as_ref()).collect();
+        stops.sort_by(|a, b| a.id.cmp(&b.id));
+        stops
+    }
+
+    /// Routes sorted by id
+    pub fn routes_ordered(&self) -> Vec<&Route> {
+        let mut routes: Vec<&Route> = self.routes.values().collect();
+        routes.sort_by(|a, b| a.id.cmp(&b.id));
+        routes
+    }
+
+    /// Trips sorted by id
+    pub fn trips_ordered(&self) -> Vec<&Trip> {
+        let mut trips: Vec<&Trip> = self.trips.values().collect();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+        trips
+    }
+
+    /// Topmost station above a stop following the `parent_station` links, or the stop itself without parent station
+    ///
+    /// The climb stops at the first ancestor that is not a station (`LocationType::StopArea`).
+    /// Returns `None` for an unknown stop or when the links form a cycle
+    pub fn root_station(&self, stop_id: &str) -> Option<&Arc<Stop>> {
+        let ancestry = self.stop_ancestry(stop_id)?;
+        let stations = ancestry[1..]
+            .iter()
+            .take_while(|stop| stop.location_type == LocationType::StopArea);
+        Some(stations.last().unwrap_or(&ancestry[0]))
+    }
+
+    /// Number of `parent_station` links above a stop, 0 for a stop without parent
+    ///
+    /// Returns `None` for an unknown stop or when the links form a cycle
+    pub fn stop_depth(&self, stop_id: &str) -> Option<usize> {
+        self.stop_ancestry(stop_id)
+            .map(|ancestry| ancestry.len() - 1)
+    }
+
+    /// The stop followed by its known ancestors, `None` if unknown or on a cycle
+    fn stop_ancestry(&self, stop_id: &str) -> Option<Vec<&Arc<Stop>>> {
+        let mut ancestry = vec![self.stops.get(stop_id)?];
+        let mut visited = HashSet::new();
+        while let Some(stop) = ancestry.last() {
+            if !visited.insert(stop.id.as_str()) {
+                return None;
+            }
+            match stop
+                .parent_station
+                .as_ref()
+                .and_then(|parent| self.stops.get(parent))
+            {
+                Some(parent) => ancestry.push(parent),
+                None => break,
+            }
+        }
+        Some(ancestry)
+    }
+
+    /// Wheelchair boarding of a stop, inherited from its parent station when unknown
+    pub fn effective_wheelchair_boarding(&self, stop_id: &str) -> Availability {
+        let stop = match self.stops.get(stop_id) {
+            Some(stop) => stop,
+            None => return Availability::InformationNotAvailable,
+        };
+        match stop.wheelchair_boarding {
+            Availability::InformationNotAvailable => stop
+                .parent_station
+                .as_ref()
+                .and_then(|parent| self.stops.get(parent))
+                .map(|parent| parent.wheelchair_boarding)
+                .unwrap_or_default(),
+            availability => availability,
+        }
+    }
+
+    pub fn trips_for_route<'a>(&'a self, route_id: &str) -> Vec<&'a Trip> {
+        self.trips
+            .values()
+            .filter(|trip| trip.route_id == route_id)
+            .collect()
+    }
+
+    /// Trips of a route ordered by their start time, trips without time come last
+    pub fn trips_for_route_sorted<'a>(&'a self, route_id: &str) -> Vec<&'a Trip> {
+        let mut trips = self.trips_for_route(route_id);
+        trips.sort_by_key(|trip| match trip.start_time() {
+            Some(time) => (false, time),
+            None => (true, 0),
+        });
+        trips
+    }
+
+    /// Trips shown to riders with this `trip_short_name`, like a train number, sorted by id
+    pub fn trips_by_short_name<'a>(&'a self, name: &str) -> Vec<&'a Trip> {
+        let mut trips: Vec<&Trip> = self
+            .trips
+            .values()
+            .filter(|trip| trip.trip_short_name.as_deref() == Some(name))
+            .collect();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+        trips
+    }
+
+    /// Trip ids grouped by their sequence of stop ids, each group sorted
+    ///
+    /// Relies on the stop times being sorted, which is the case unless disabled in `GtfsReader`
+    pub fn trip_patterns(&self) -> HashMap<Vec<String>, Vec<String>> {
+        let mut patterns: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+        for trip in self.trips.values() {
+            let pattern = trip
+                .stop_times
+                .iter()
+                .map(|st| st.stop.id.to_owned())
+                .collect();
+            patterns
+                .entry(pattern)
+                .or_default()
+                .push(trip.id.to_owned());
+        }
+        for trip_ids in patterns.values_mut() {
+            trip_ids.sort();
+        }
+        patterns
+    }
+
+    /// Trips operated by the same vehicle, sorted by start time
+    pub fn trips_in_block<'a>(&'a self, block_id: &str) -> Vec<&'a Trip> {
+        let mut trips: Vec<&Trip> = self
+            .trips
+            .values()
+            .filter(|trip| trip.block_id.as_deref() == Some(block_id))
+            .collect();
+        trips.sort_by_key(|trip| match trip.start_time() {
+            Some(time) => (false, time),
+            None => (true, 0),
+        });
+        trips
+    }
+
+    /// Pairs of trips of the same block and service whose schedules overlap, as a vehicle cannot run both
+    ///
+    /// Each pair gives the trip starting first; a trip may start when the previous one ends
+    pub fn validate_block_overlaps(&self) -> Vec<(String, String)> {
+        let mut blocks: HashMap<_, Vec<_>> = HashMap::new();
+        for trip in self.trips.values() {
+            if let (Some(block_id), Some(start), Some(end)) =
+                (trip.block_id.as_deref(), trip.start_time(), trip.end_time())
+            {
+                blocks
+                    .entry((block_id, trip.service_id.as_str()))
+                    .or_default()
+                    .push((start, end, trip.id.as_str()));
+            }
+        }
+        let mut overlaps = Vec::new();
+        for trips in blocks.values_mut() {
+            trips.sort();
+            for (i, (_, end, id)) in trips.iter().enumerate() {
+                for (other_start, _, other_id) in &trips[i + 1..] {
+                    if other_start >= end {
+                        break;
+                    }
+                    overlaps.push((id.to_string(), other_id.to_string()));
+                }
+            }
+        }
+        overlaps.sort();
+        overlaps
+    }
+
+    /// Whether some trips are defined by `frequencies.txt`, and need to be expanded to get their actual times
+    pub fn is_frequency_based(&self) -> bool {
+        self.frequencies
+            .values()
+            .any(|frequencies| !frequencies.is_empty())
+    }
+
+    /// Number of distinct stops served by the trips of a route
+    pub fn route_stop_count(&self, route_id: &str) -> usize {
+        self.trips
+            .values()
+            .filter(|trip| trip.route_id == route_id)
+            .flat_map(|trip| trip.stop_times.iter().map(|st| st.stop.id.as_str()))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Trip of a route with the most stop times, giving the canonical stop list of the route
+    ///
+    /// Ties are broken by the longest shape, then by the smallest trip id
+    pub fn representative_trip(&self, route_id: &str) -> Option<&Trip> {
+        self.trips
+            .values()
+            .filter(|trip| trip.route_id == route_id)
+            .max_by(|a, b| {
+                let shape_length = |trip: &Trip| {
+                    trip.shape_id
+                        .as_deref()
+                        .and_then(|shape_id| self.shape_length(shape_id))
+                        .unwrap_or(0.0)
+                };
+                a.stop_times
+                    .len()
+                    .cmp(&b.stop_times.len())
+                    .then_with(|| {
+                        shape_length(a)
+                            .partial_cmp(&shape_length(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .then_with(|| b.id.cmp(&a.id))
+            })
+    }
+
+    /// First and last stops of the representative trip of a route
+    pub fn route_terminals(&self, route_id: &str) -> Option<(&Stop, &Stop)> {
+        let trip = self.representative_trip(route_id)?;
+        Some((trip.first_stop()?, trip.last_stop()?))
+    }
+
+    /// Routes operated by an agency, sorted by id
+    ///
+    /// When the feed has a single agency, the routes without `agency_id` belong to it
+    pub fn routes_for_agency(&self, agency_id: &str) -> Vec<&Route> {
+        let mut routes: Vec<&Route> = self
+            .routes
+            .values()
+            .filter(|route| match &route.agency_id {
+                Some(id) => same_agency_id(id, agency_id),
+                None => match self.route_agency(&route.id) {
+                    Some(Agency { id: Some(id), .. }) => same_agency_id(id, agency_id),
+                    Some(_) => true,
+                    None => false,
+                },
+            })
+            .collect();
+        routes.sort_by(|a, b| a.id.cmp(&b.id));
+        routes
+    }
+
+    /// Distinct routes having at least one trip calling at the stop
+    ///
+    /// Nothing is cached, since the fields of `Gtfs` can be modified at any time:
+    /// each call scans the stop times of every trip
+    pub fn routes_at_stop(&self, stop_id: &str) -> Vec<&Route> {
+        let route_ids: HashSet<&str> = self
+            .trips
+            .values()
+            .filter(|trip| trip.stop_times.iter().any(|st| st.stop.id == stop_id))
+            .map(|trip| trip.route_id.as_str())
+            .collect();
+        let mut routes: Vec<&Route> = route_ids
+            .into_iter()
+            .filter_map(|id| self.routes.get(id))
+            .collect();
+        routes.sort_by(|a, b| a.id.cmp(&b.id));
+        routes
+    }
+
+    /// Trips calling at `from_stop` and later, with a higher `stop_sequence`, at `to_stop`
+    pub fn trips_between_stops(&self, from_stop: &str, to_stop: &str) -> Vec<&Trip> {
+        let mut trips: Vec<&Trip> = self
+            .trips
+            .values()
+            .filter(|trip| {
+                let from = trip
+                    .stop_times
+                    .iter()
+                    .filter(|st| st.stop.id == from_stop)
+                    .map(|st| st.stop_sequence)
+                    .min();
+                let to = trip
+                    .stop_times
+                    .iter()
+                    .filter(|st| st.stop.id == to_stop)
+                    .map(|st| st.stop_sequence)
+                    .max();
+                match (from, to) {
+                    (Some(from), Some(to)) => from < to,
+                    _ => false,
+                }
+            })
+            .collect();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+        trips
+    }
+
+    /// Stop times, as (trip_id, stop_sequence), with an arrival or departure after 48 hours,
+    /// which is rather a data error than a trip running over two days
+    pub fn validate_time_bounds(&self) -> Vec<(String, u16)> {
+        const MAX_TIME: u32 = 48 * 3600;
+        let mut stop_times: Vec<(String, u16)> = self
+            .trips
+            .values()
+            .flat_map(|trip| {
+                trip.stop_times
+                    .iter()
+                    .filter(|st| {
+                        st.arrival_time
+                            .into_iter()
+                            .chain(st.departure_time)
+                            .any(|time| time > MAX_TIME)
+                    })
+                    .map(move |st| (trip.id.to_owned(), st.stop_sequence))
+            })
+            .collect();
+        stop_times.sort();
+        stop_times
+    }
+
+    /// Ids of the trips with a continuous pickup or drop off, from their route or a stop time,
+    /// that still give a `pickup_type` or `drop_off_type` to their stop times
+    pub fn validate_continuous_consistency(&self) -> Vec<String> {
+        let enabled =
+            |value: ContinuousPickupDropOff| value != ContinuousPickupDropOff::NotAvailable;
+        let mut ids: Vec<String> = self
+            .trips
+            .values()
+            .filter(|trip| {
+                let route = self.routes.get(&trip.route_id);
+                let continuous_pickup = route.map_or(false, |r| enabled(r.continuous_pickup))
+                    || trip
+                        .stop_times
+                        .iter()
+                        .any(|st| st.continuous_pickup.map_or(false, enabled));
+                let continuous_drop_off = route.map_or(false, |r| enabled(r.continuous_drop_off))
+                    || trip
+                        .stop_times
+                        .iter()
+                        .any(|st| st.continuous_drop_off.map_or(false, enabled));
+                (continuous_pickup && trip.stop_times.iter().any(|st| st.pickup_type.is_some()))
+                    || (continuous_drop_off
+                        && trip.stop_times.iter().any(|st| st.drop_off_type.is_some()))
+            })
+            .map(|trip| trip.id.to_owned())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Ids of the routes whose text color does not have the WCAG AA contrast ratio (4.5:1) with the route color
+    pub fn validate_color_contrast(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .routes
+            .values()
+            .filter(|route| route.color.contrast_ratio(route.text_color) < 4.5)
+            .map(|route| route.id.to_owned())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Warnings about the agencies whose `agency_lang` is not a well-formed language tag
+    pub fn validate_languages(&self) -> Vec<String> {
+        self.agencies
+            .iter()
+            .filter(|agency| agency.lang.is_some() && agency.language_tag().is_none())
+            .map(|agency| {
+                format!(
+                    "Invalid agency_lang {} for the agency {}",
+                    agency.lang.as_deref().unwrap_or_default(),
+                    agency.name
+                )
+            })
+            .collect()
+    }
+
+    /// Groups of stop ids sharing a name, when some of these stops are more than `max_distance_m` meters apart
+    ///
+    /// Close stops with the same name, like the platforms of both directions, are expected and not reported
+    pub fn duplicate_stop_names(&self, max_distance_m: f64) -> Vec<Vec<String>> {
+        let mut groups: HashMap<&str, Vec<&Stop>> = HashMap::new();
+        for stop in self.stops.values() {
+            groups.entry(stop.name.as_str()).or_default().push(stop);
+        }
+        let mut duplicates: Vec<Vec<String>> = groups
+            .into_values()
+            .filter(|stops| {
+                stops.iter().enumerate().any(|(i, a)| {
+                    stops[i + 1..].iter().any(|b| {
+                        haversine_distance((a.latitude, a.longitude), (b.latitude, b.longitude))
+                            > max_distance_m
+                    })
+                })
+            })
+            .map(|stops| {
+                let mut ids: Vec<String> = stops.iter().map(|stop| stop.id.to_owned()).collect();
+                ids.sort();
+                ids
+            })
+            .collect();
+        duplicates.sort();
+        duplicates
+    }
+
+    /// Groups of route ids sharing the same names, route type and agency
+    pub fn find_duplicate_routes(&self) -> Vec<Vec<String>> {
+        let mut groups: HashMap<_, Vec<String>> = HashMap::new();
+        for route in self.routes.values() {
+            groups
+                .entry((
+                    route.short_name.as_str(),
+                    route.long_name.as_str(),
+                    route.route_type,
+                    route.agency_id.as_deref(),
+                ))
+                .or_default()
+                .push(route.id.to_owned());
+        }
+        let mut duplicates: Vec<Vec<String>> = groups
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .map(|mut ids| {
+                ids.sort();
+                ids
+            })
+            .collect();
+        duplicates.sort();
+        duplicates
+    }
+
+    pub fn routes_in_network(&self, network_id: &str) -> Vec<&Route> {
+        self.routes
+            .values()
+            .filter(|route| route.network_id.as_deref() == Some(network_id))
+            .collect()
+    }
+
+    pub fn routes_with_trips(&self) -> impl Iterator<Item = (&Route, Vec<&Trip>)> {
+        let mut trips_by_route: HashMap<&str, Vec<&Trip>> = HashMap::new();
+        for trip in self.trips.values() {
+            trips_by_route
+                .entry(trip.route_id.as_str())
+                .or_default()
+                .push(trip);
+        }
+        self.routes.values().map(move |route| {
+            let trips = trips_by_route.remove(route.id.as_str()).unwrap_or_default();
+            (route, trips)
+        })
+    }
+
+    /// Returns the UTC instant a trip leaves a stop on a given service date
+    ///
+    /// The stop timezone is used, or the timezone of the trip's agency when the stop has none
+    #[cfg(feature = "timezone")]
+    pub fn departure_datetime(
+        &self,
+        trip_id: &str,
+        stop_sequence: u16,
+        date: NaiveDate,
+    ) -> Option<DateTime<Utc>> {
+        let trip = self.trips.get(trip_id)?;
+        let stop_time = trip
+            .stop_times
+            .iter()
+            .find(|st| st.stop_sequence == stop_sequence)?;
+        self.localize(trip, stop_time, stop_time.departure_time?, date)
+    }
+
+    /// Converts a time of a stop time, in seconds since midnight of the service date, to UTC
+    #[cfg(feature = "timezone")]
+    fn localize(
+        &self,
+        trip: &Trip,
+        stop_time: &StopTime,
+        seconds: u32,
+        date: NaiveDate,
+    ) -> Option<DateTime<Utc>> {
+        use chrono::LocalResult;
+
+        let timezone: chrono_tz::Tz = stop_time
+            .stop
+            .timezone
+            .as_ref()
+            .or_else(|| self.trip_agency(trip).map(|agency| &agency.timezone))?
+            .parse()
+            .ok()?;
+
+        // Times after midnight belong to the following days
+        let date = date + Duration::days(i64::from(seconds / 86_400));
+        let time = NaiveTime::from_num_seconds_from_midnight_opt(seconds % 86_400, 0)?;
+        let local = date.and_time(time);
+        match timezone.from_local_datetime(&local) {
+            LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => Some(dt.with_timezone(&Utc)),
+            // The local time falls in a DST gap: shift it with the offset in effect just before
+            LocalResult::None => timezone
+                .from_local_datetime(&(local - Duration::hours(1)))
+                .earliest()
+                .map(|dt| (dt + Duration::hours(1)).with_timezone(&Utc)),
+        }
+    }
+
+    /// Applies the delays of a realtime trip update to the scheduled stop times of its trip
+    ///
+    /// A delay is propagated to the following stops until another update is given.
+    /// Absolute `time` values require the `start_date` of the update's trip descriptor to be set.
+    #[cfg(feature = "rt")]
+    pub fn apply_trip_update(&self, update: &gtfs_realtime::TripUpdate) -> Vec<AdjustedStopTime> {
+        let trip = match update
+            .trip
+            .trip_id
+            .as_ref()
+            .and_then(|id| self.trips.get(id))
+        {
+            Some(trip) => trip,
+            None => return Vec::new(),
+        };
+        let start_date = update
+            .trip
+            .start_date
+            .as_ref()
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok());
+        let event_delay = |delay: Option<i32>,
+                           time: Option<i64>,
+                           stop_time: &StopTime,
+                           scheduled: Option<u32>| {
+            delay.map(i64::from).or_else(|| {
+                let scheduled = self.localize(trip, stop_time, scheduled?, start_date?)?;
+                Some(time? - scheduled.timestamp())
+            })
+        };
+        let shift =
+            |time: Option<u32>, delay: i64| time.map(|t| (i64::from(t) + delay).max(0) as u32);
+
+        let mut delay = 0;
+        let mut result = Vec::with_capacity(trip.stop_times.len());
+        for stop_time in &trip.stop_times {
+            let stop_update = update
+                .stop_time_update
+                .iter()
+                .find(|u| match u.stop_sequence {
+                    Some(sequence) => sequence == u32::from(stop_time.stop_sequence),
+                    None => u.stop_id.as_ref() == Some(&stop_time.stop.id),
+                });
+            let mut arrival_delay = delay;
+            if let Some(stop_update) = stop_update {
+                if let Some(d) = stop_update
+                    .arrival
+                    .as_ref()
+                    .and_then(|e| event_delay(e.delay, e.time, stop_time, stop_time.arrival_time))
+                {
+                    arrival_delay = d;
+                }
+                delay = stop_update
+                    .departure
+                    .as_ref()
+                    .and_then(|e| event_delay(e.delay, e.time, stop_time, stop_time.departure_time))
+                    .unwrap_or(arrival_delay);
+            } else {
+                delay = arrival_delay;
+            }
+            result.push(AdjustedStopTime {
+                stop_sequence: stop_time.stop_sequence,
+                stop_id: stop_time.stop.id.to_owned(),
+                arrival_time: shift(stop_time.arrival_time, arrival_delay),
+                departure_time: shift(stop_time.departure_time, delay),
+                arrival_delay,
+                departure_delay: delay,
+            });
+        }
+        result
+    }
+
+    /// Directed graph whose nodes are all the stops, sorted by id
+    ///
+    /// Each pair of consecutive stop times of a trip adds a `Connection` edge, so stops served
+    /// by several trips are linked by several edges. Each transfer adds a `Transfer` edge,
+    /// except the ones with `TransferType::NotPossible` or not between two known stops.
+    #[cfg(feature = "graph")]
+    pub fn to_stop_graph(&self) -> petgraph::Graph<&Stop, EdgeWeight> {
+        let mut graph = petgraph::Graph::new();
+        let nodes: HashMap<&str, petgraph::graph::NodeIndex> = self
+            .stops_ordered()
+            .into_iter()
+            .map(|stop| (stop.id.as_str(), graph.add_node(stop)))
+            .collect();
+        for trip in self.trips_ordered() {
+            for pair in trip.stop_times.windows(2) {
+                let (from, to) = match (
+                    nodes.get(pair[0].stop.id.as_str()),
+                    nodes.get(pair[1].stop.id.as_str()),
+                ) {
+                    (Some(from), Some(to)) => (*from, *to),
+                    _ => continue,
+                };
+                let travel_time = match (
+                    pair[0].departure_time.or(pair[0].arrival_time),
+                    pair[1].arrival_time.or(pair[1].departure_time),
+                ) {
+                    (Some(departure), Some(arrival)) => Some(arrival.saturating_sub(departure)),
+                    _ => None,
+                };
+                graph.add_edge(from, to, EdgeWeight::Connection { travel_time });
+            }
+        }
+        for transfer in &self.transfers {
+            if transfer.transfer_type == TransferType::NotPossible {
+                continue;
+            }
+            if let (Some(from), Some(to)) = (
+                transfer
+                    .from_stop_id
+                    .as_deref()
+                    .and_then(|id| nodes.get(id)),
+                transfer.to_stop_id.as_deref().and_then(|id| nodes.get(id)),
+            ) {
+                graph.add_edge(
+                    *from,
+                    *to,
+                    EdgeWeight::Transfer {
+                        min_transfer_time: transfer.min_transfer_time,
+                    },
+                );
+            }
+        }
+        graph
+    }
+
+    /// A trip with its route and the agency operating the route, `None` if the trip or its route is missing
+    ///
+    /// A route without `agency_id` is operated by the agency of the feed when it has only one
+    pub fn trip_context(&self, trip_id: &str) -> Option<(&Trip, &Route, Option<&Agency>)> {
+        let trip = self.trips.get(trip_id)?;
+        let route = self.routes.get(&trip.route_id)?;
+        Some((trip, route, self.route_agency(&route.id)))
+    }
+
+    /// Agency operating the trip of a stop time, to localize its time; `None` if any link is missing
+    pub fn stop_time_agency(&self, trip_id: &str, stop_sequence: u16) -> Option<&Agency> {
+        let (trip, _, agency) = self.trip_context(trip_id)?;
+        trip.stop_time_at_sequence(stop_sequence)?;
+        agency
+    }
+
+    #[cfg(feature = "timezone")]
+    fn trip_agency(&self, trip: &Trip) -> Option<&Agency> {
+        self.route_agency(&trip.route_id)
+            .or_else(|| self.agencies.first())
+    }
+
+    /// Agency operating a route, a route without `agency_id` being operated by the agency of the feed when it has only one
+    fn route_agency(&self, route_id: &str) -> Option<&Agency> {
+        match (
+            &self.routes.get(route_id)?.agency_id,
+            self.agencies.as_slice(),
+        ) {
+            (Some(agency_id), _) => self.get_agency(agency_id).ok(),
+            (None, [agency]) => Some(agency),
+            (None, _) => None,
+        }
+    }
+
+    /// Timezone of a stop
+    ///
+    /// In order of precedence: the `stop_timezone` of the stop, the timezone of the agency
+    /// operating a route serving the stop, and finally the timezone of the first agency
+    pub fn stop_timezone(&self, stop_id: &str) -> Option<&str> {
+        let stop = self.stops.get(stop_id)?;
+        if let Some(timezone) = &stop.timezone {
+            return Some(timezone);
+        }
+        self.trips
+            .values()
+            .filter(|trip| trip.stop_times.iter().any(|st| st.stop.id == stop_id))
+            .find_map(|trip| self.route_agency(&trip.route_id))
+            .or_else(|| self.agencies.first())
+            .map(|agency| agency.timezone.as_str())
+    }
+
+    pub fn agencies_sorted(&self) -> Vec<&Agency> {
+        let mut agencies: Vec<&Agency> = self.agencies.iter().collect();
+        agencies.sort_by(|a, b| a.name.cmp(&b.name));
+        agencies
+    }
+
+    /// Human readable description of when a service runs
+    pub fn service_summary(&self, service_id: &str) -> String {
+        let summary = match self.calendar.get(service_id) {
+            Some(calendar) => calendar.weekday_summary(),
+            None => "No weekly service".to_owned(),
+        };
+        let has_exceptions = self
+            .calendar_dates
+            .get(service_id)
+            .map_or(false, |dates| !dates.is_empty());
+        if has_exceptions {
+            format!("{} (with exceptions)", summary)
+        } else {
+            summary
+        }
+    }
+
+    pub fn get_stop<'a>(&'a self, id: &str) -> Result<&'a Stop, ReferenceError> {
+        match self.stops.get(id) {
+            Some(stop) => Ok(stop),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    pub fn get_trip<'a>(&'a self, id: &str) -> Result<&'a Trip, ReferenceError> {
+        match self.trips.get(id) {
+            Some(trip) => Ok(trip),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    /// Finds an agency by id, ignoring the spaces around the ids so that `"1 "` matches `"1"`
+    pub fn get_agency<'a>(&'a self, id: &str) -> Result<&'a Agency, ReferenceError> {
+        self.agencies
+            .iter()
+            .find(|agency| matches!(&agency.id, Some(agency_id) if same_agency_id(agency_id, id)))
+            .ok_or_else(|| ReferenceError { id: id.to_owned() })
+    }
+
+    pub fn get_route<'a>(&'a self, id: &str) -> Result<&'a Route, ReferenceError> {
+        match self.routes.get(id) {
+            Some(route) => Ok(route),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    pub fn get_shape<'a>(&'a self, id: &str) -> Result<&'a Vec<Shape>, ReferenceError> {
+        match self.shapes.get(id) {
+            Some(shape) => Ok(shape),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    pub fn get_fare_attribute<'a>(&'a self, id: &str) -> Result<&'a FareAttribute, ReferenceError> {
+        match self.fare_attributes.get(id) {
+            Some(fare_attribute) => Ok(fare_attribute),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    pub fn get_fare_product<'a>(&'a self, id: &str) -> Result<&'a FareProduct, ReferenceError> {
+        match self.fare_products.get(id) {
+            Some(fare_product) => Ok(fare_product),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    pub fn get_location_group<'a>(&'a self, id: &str) -> Result<&'a LocationGroup, ReferenceError> {
+        match self.location_groups.get(id) {
+            Some(location_group) => Ok(location_group),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    /// Stops of a location group, in the order of `location_group_stops.txt`, ignoring unknown stops
+    pub fn stops_in_location_group(&self, group_id: &str) -> Vec<&Arc<Stop>> {
+        self.location_group_stops
+            .get(group_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|stop_id| self.stops.get(stop_id))
+            .collect()
+    }
+
+    pub fn get_booking_rule<'a>(&'a self, id: &str) -> Result<&'a BookingRule, ReferenceError> {
+        match self.booking_rules.get(id) {
+            Some(booking_rule) => Ok(booking_rule),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    /// Returns the ids of the fares whose currency is not an ISO 4217 code
+    pub fn validate_currencies(&self) -> Vec<String> {
+        let mut invalid: Vec<String> = self
+            .fare_attributes
+            .values()
+            .filter(|fare| !fare.has_valid_currency())
+            .map(|fare| fare.id.to_owned())
+            .collect();
+        invalid.sort();
+        invalid
+    }
+
+    /// Returns the fares that can be used on a route between two optional zones
+    ///
+    /// A fare applies if at least one of its rules matches, or if it has no rule at all
+    pub fn applicable_fares(
+        &self,
+        route_id: &str,
+        origin_zone: Option<&str>,
+        dest_zone: Option<&str>,
+    ) -> Vec<&FareAttribute> {
+        self.fare_attributes
+            .values()
+            .filter(|fare| match self.fare_rules.get(&fare.id) {
+                None => true,
+                Some(rules) => rules
+                    .iter()
+                    .any(|rule| rule.matches(route_id, origin_zone, dest_zone)),
+            })
+            .collect()
+    }
+
+    /// The (latitude, longitude) points of a shape, ordered by sequence
+    pub fn shape_polyline(&self, shape_id: &str) -> Option<Vec<(f64, f64)>> {
+        self.shapes.get(shape_id).map(|shape| {
+            shape
+                .iter()
+                .map(|point| (point.latitude, point.longitude))
+                .collect()
+        })
+    }
+
+    /// The stop times of a trip in the `stop_times.txt` format, with headers
+    pub fn trip_stop_times_csv(&self, trip_id: &str) -> Option<String> {
+        let trip = self.trips.get(trip_id)?;
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for st in &trip.stop_times {
+            let row = StopTimeRow {
+                trip_id: trip.id.to_owned(),
+                arrival_time: st.arrival_time,
+                departure_time: st.departure_time,
+                stop_id: st.stop.id.to_owned(),
+                stop_sequence: st.stop_sequence,
+                pickup_type: st.pickup_type,
+                drop_off_type: st.drop_off_type,
+                shape_dist_traveled: st.shape_dist_traveled,
+                continuous_pickup: st.continuous_pickup,
+                continuous_drop_off: st.continuous_drop_off,
+            };
+            writer.serialize(row).ok()?;
+        }
+        String::from_utf8(writer.into_inner().ok()?).ok()
+    }
+
+    /// Trips without a `shape_id` or referencing an unknown shape, sorted by id
+    pub fn trips_missing_shapes(&self) -> Vec<&Trip> {
+        let mut trips: Vec<&Trip> = self
+            .trips
+            .values()
+            .filter(|trip| match &trip.shape_id {
+                Some(shape_id) => self.shape_length(shape_id).is_none(),
+                None => true,
+            })
+            .collect();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+        trips
+    }
+
+    /// Converts the `shape_dist_traveled` of shape points and stop times from `unit` to meters
+    pub fn normalize_shape_distances(&mut self, unit: DistanceUnit) {
+        let factor = unit.meters();
+        let to_meters = |dist: &mut Option<f32>| {
+            if let Some(dist) = dist {
+                *dist = (f64::from(*dist) * factor) as f32;
+            }
+        };
+        for point in self.shapes.values_mut().flatten() {
+            to_meters(&mut point.dist_traveled);
+        }
+        for trip in self.trips.values_mut() {
+            for st in &mut trip.stop_times {
+                to_meters(&mut st.shape_dist_traveled);
+            }
+        }
+    }
+
+    /// Length of a shape in meters, following its points
+    pub fn shape_length(&self, shape_id: &str) -> Option<f64> {
+        match self.shapes.get(shape_id) {
+            Some(shape) => Some(ShapeSummary::of_points(shape).length),
+            None => self
+                .shape_lengths
+                .get(shape_id)
+                .or_else(|| self.shape_summaries.get(shape_id).map(|s| &s.length))
+                .copied(),
+        }
+    }
+
+    /// Length of a trip in meters
+    ///
+    /// The shape of the trip is used if it has one, otherwise the distance between its stops
+    pub fn trip_distance(&self, trip_id: &str) -> Option<f64> {
+        let trip = self.trips.get(trip_id)?;
+        if let Some(length) = trip
+            .shape_id
+            .as_ref()
+            .and_then(|shape_id| self.shape_length(shape_id))
+        {
+            return Some(length);
+        }
+        if trip.stop_times.len() < 2 {
+            return None;
+        }
+        Some(
+            trip.stop_times
+                .windows(2)
+                .map(|pair| {
+                    haversine_distance(
+                        (pair[0].stop.latitude, pair[0].stop.longitude),
+                        (pair[1].stop.latitude, pair[1].stop.longitude),
+                    )
+                })
+                .sum(),
+        )
+    }
+
+    /// Points to draw a trip, from its shape or else from the position of its stops
+    ///
+    /// Stops without coordinates are skipped
+    pub fn trip_geometry(&self, trip_id: &str) -> Option<Vec<(f64, f64)>> {
+        let trip = self.trips.get(trip_id)?;
+        if let Some(points) = trip
+            .shape_id
+            .as_ref()
+            .and_then(|shape_id| self.shape_polyline(shape_id))
+        {
+            return Some(points);
+        }
+        Some(
+            trip.stop_times
+                .iter()
+                .filter(|st| st.stop.latitude != 0.0 || st.stop.longitude != 0.0)
+                .map(|st| (st.stop.latitude, st.stop.longitude))
+                .collect(),
+        )
+    }
+
+    pub fn get_calendar<'a>(&'a self, id: &str) -> Result<&'a Calendar, ReferenceError> {
+        match self.calendar.get(id) {
+            Some(calendar) => Ok(calendar),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    pub fn get_calendar_date<'a>(
+        &'a self,
+        id: &str,
+    ) -> Result<&'a Vec<CalendarDate>, ReferenceError> {
+        match self.calendar_dates.get(id) {
+            Some(calendar_dates) => Ok(calendar_dates),
+            None => Err(ReferenceError { id: id.to_owned() }),
+        }
+    }
+
+    /// Like `get_stop`, but falls back to a case-insensitive scan of all the stops (O(n))
+    pub fn get_stop_ci<'a>(&'a self, id: &str) -> Result<&'a Stop, ReferenceError> {
+        get_case_insensitive(&self.stops, id).map(|stop| stop.as_ref())
+    }
+
+    /// Like `get_trip`, but falls back to a case-insensitive scan of all the trips (O(n))
+    pub fn get_trip_ci<'a>(&'a self, id: &str) -> Result<&'a Trip, ReferenceError> {
+        get_case_insensitive(&self.trips, id)
+    }
+
+    /// Like `get_route`, but falls back to a case-insensitive scan of all the routes (O(n))
+    pub fn get_route_ci<'a>(&'a self, id: &str) -> Result<&'a Route, ReferenceError> {
+        get_case_insensitive(&self.routes, id)
+    }
+
+    /// Like `get_calendar`, but falls back to a case-insensitive scan of all the calendars (O(n))
+    pub fn get_calendar_ci<'a>(&'a self, id: &str) -> Result<&'a Calendar, ReferenceError> {
+        get_case_insensitive(&self.calendar, id)
+    }
+}
+
+fn get_case_insensitive<'a, T>(
+    map: &'a HashMap<String, T>,
+    id: &str,
+) -> Result<&'a T, ReferenceError> {
+    map.get(id)
+        .or_else(|| {
+            map.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(id))
+                .map(|(_, value)| value)
+        })
+        .ok_or_else(|| ReferenceError { id: id.to_owned() })
+}
+
+fn prefixed_keys<T>(map: HashMap<String, T>, prefix: &str) -> HashMap<String, T> {
+    map.into_iter()
+        .map(|(id, value)| (format!("{}{}", prefix, id), value))
+        .collect()
+}
+
+fn map_content_eq<T, F>(a: &HashMap<String, T>, b: &HashMap<String, T>, eq: F) -> bool
+where
+    F: Fn(&T, &T) -> bool,
+{
+    a.len() == b.len()
+        && a.iter()
+            .all(|(id, value)| b.get(id).map_or(false, |other| eq(value, other)))
+}
+
+/// Whether both slices hold the same elements, in any order
+fn same_elements<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+    let mut used = vec![false; b.len()];
+    a.len() == b.len()
+        && a.iter()
+            .all(|x| match (0..b.len()).find(|&i| !used[i] && b[i] == *x) {
+                Some(i) => {
+                    used[i] = true;
+                    true
+                }
+                None => false,
+            })
+}
+
+fn sorted_by_key<T, K: Ord, F: Fn(&T) -> K>(values: &[T], key: F) -> Vec<&T> {
+    let mut sorted: Vec<&T> = values.iter().collect();
+    sorted.sort_by_key(|value| key(value));
+    sorted
+}
+
+fn trip_content_eq(a: &Trip, b: &Trip) -> bool {
+    let Trip {
+        id,
+        service_id,
+        route_id,
+        trip_short_name,
+        shape_id,
+        block_id,
+        wheelchair_accessible,
+        bikes_allowed,
+        stop_times,
+    } = a;
+    *id == b.id
+        && *service_id == b.service_id
+        && *route_id == b.route_id
+        && *trip_short_name == b.trip_short_name
+        && *shape_id == b.shape_id
+        && *block_id == b.block_id
+        && *wheelchair_accessible == b.wheelchair_accessible
+        && *bikes_allowed == b.bikes_allowed
+        && same_elements(stop_times, &b.stop_times)
+}
+
+/// Whether two agency ids are the same, ignoring the spaces around them
+fn same_agency_id(a: &str, b: &str) -> bool {
+    a.trim() == b.trim()
+}
+
+/// 64-bit FNV-1a, whose output does not depend on the Rust version unlike `DefaultHasher`
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Hashes of the items, sorted so that they do not depend on the order of the items
+fn sorted_hashes<T: Serialize>(items: impl IntoIterator<Item = T>) -> Vec<u64> {
+    let mut hashes: Vec<u64> = items.into_iter().map(|item| hash_of(&item)).collect();
+    hashes.sort_unstable();
+    hashes
+}
+
+fn hash_of<T: Serialize + ?Sized>(value: &T) -> u64 {
+    let mut hasher = Fnv1a::default();
+    // Hashing cannot fail, only the custom serializers of the crate could return an error
+    let _ = value.serialize(HashSerializer(&mut hasher));
+    hasher.finish()
+}
+
+fn trip_hash(trip: &Trip) -> u64 {
+    let Trip {
+        id,
+        service_id,
+        route_id,
+        trip_short_name,
+        shape_id,
+        block_id,
+        wheelchair_accessible,
+        bikes_allowed,
+        stop_times,
+    } = trip;
+    hash_of(&(
+        id,
+        service_id,
+        route_id,
+        trip_short_name,
+        shape_id,
+        block_id,
+        wheelchair_accessible,
+        bikes_allowed,
+        sorted_hashes(stop_times),
+    ))
+}
+
+/// Serializer writing the values to a `Fnv1a`, so that the hash follows the fields and not a `Debug` output
+///
+/// Each value is preceded by a tag of its kind, and the entries of a map are hashed whatever their order
+struct HashSerializer<'a>(&'a mut Fnv1a);
+
+/// Entries of a map, hashed one by one and written sorted at the end
+struct HashMapEntries<'a> {
+    hasher: &'a mut Fnv1a,
+    entry: Fnv1a,
+    entries: Vec<u64>,
+}
+
+impl<'a> serde::Serializer for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = HashMapEntries<'a>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), fmt::Error> {
+        self.tagged(0, &[v as u8])
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), fmt::Error> {
+        self.serialize_i64(v.into())
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), fmt::Error> {
+        self.serialize_i64(v.into())
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), fmt::Error> {
+        self.serialize_i64(v.into())
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), fmt::Error> {
+        self.tagged(1, &v.to_le_bytes())
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), fmt::Error> {
+        self.serialize_u64(v.into())
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), fmt::Error> {
+        self.serialize_u64(v.into())
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), fmt::Error> {
+        self.serialize_u64(v.into())
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), fmt::Error> {
+        self.tagged(2, &v.to_le_bytes())
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), fmt::Error> {
+        self.serialize_f64(v.into())
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), fmt::Error> {
+        self.tagged(3, &v.to_bits().to_le_bytes())
+    }
+    fn serialize_char(self, v: char) -> Result<(), fmt::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_str(self, v: &str) -> Result<(), fmt::Error> {
+        self.tagged(4, v.as_bytes())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), fmt::Error> {
+        self.tagged(5, v)
+    }
+    fn serialize_none(self) -> Result<(), fmt::Error> {
+        self.tagged(6, &[])
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), fmt::Error> {
+        self.0.write_u8(7);
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), fmt::Error> {
+        self.tagged(8, &[])
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), fmt::Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), fmt::Error> {
+        self.tagged(9, variant.as_bytes())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        value.serialize(self.tagged_compound(10, variant))
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, fmt::Error> {
+        Ok(self.tagged_compound(11, ""))
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self, fmt::Error> {
+        Ok(self.tagged_compound(11, ""))
+    }
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<Self, fmt::Error> {
+        Ok(self.tagged_compound(12, name))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, fmt::Error> {
+        Ok(self.tagged_compound(10, variant))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<HashMapEntries<'a>, fmt::Error> {
+        Ok(HashMapEntries {
+            hasher: self.0,
+            entry: Fnv1a::default(),
+            entries: Vec::new(),
+        })
+    }
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self, fmt::Error> {
+        Ok(self.tagged_compound(12, name))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, fmt::Error> {
+        Ok(self.tagged_compound(10, variant))
+    }
+}
+
+impl<'a> HashSerializer<'a> {
+    fn tagged(self, tag: u8, bytes: &[u8]) -> Result<(), fmt::Error> {
+        self.0.write_u8(tag);
+        self.0.write_u64(bytes.len() as u64);
+        self.0.write(bytes);
+        Ok(())
+    }
+
+    fn tagged_compound(self, tag: u8, name: &str) -> Self {
+        self.0.write_u8(tag);
+        self.0.write(name.as_bytes());
+        self.0.write_u8(0);
+        self
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        value.serialize(HashSerializer(&mut *self.0))
+    }
+
+    fn end_compound(self) -> Result<(), fmt::Error> {
+        self.0.write_u8(13);
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeSeq for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_compound()
+    }
+}
+
+impl<'a> serde::ser::SerializeTuple for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_compound()
+    }
+}
+
+impl<'a> serde::ser::SerializeTupleStruct for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_compound()
+    }
+}
+
+impl<'a> serde::ser::SerializeTupleVariant for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_compound()
+    }
+}
+
+impl<'a> serde::ser::SerializeStruct for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        self.element(key)?;
+        self.element(value)
+    }
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_compound()
+    }
+}
+
+impl<'a> serde::ser::SerializeStructVariant for HashSerializer<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        self.element(key)?;
+        self.element(value)
+    }
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_compound()
+    }
+}
+
+impl<'a> serde::ser::SerializeMap for HashMapEntries<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), fmt::Error> {
+        self.entry = Fnv1a::default();
+        key.serialize(HashSerializer(&mut self.entry))
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        value.serialize(HashSerializer(&mut self.entry))?;
+        self.entries.push(self.entry.finish());
+        Ok(())
+    }
+    fn end(mut self) -> Result<(), fmt::Error> {
+        self.entries.sort_unstable();
+        self.hasher.write_u8(14);
+        for entry in self.entries {
+            self.hasher.write_u64(entry);
+        }
+        self.hasher.write_u8(13);
+        Ok(())
+    }
+}
+
+/// Great-circle distance in meters between two (latitude, longitude) points
+pub fn haversine_distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let delta_lat = (to.0 - from.0).to_radians();
+    let delta_lon = (to.1 - from.1).to_radians();
+    let a =
+        (delta_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Encodes (latitude, longitude) points with Google's encoded polyline algorithm
+pub fn encode_polyline(points: &[(f64, f64)]) -> String {
+    let mut result = String::new();
+    let mut previous = (0, 0);
+    for &(lat, lon) in points {
+        let current = ((lat * 1e5).round() as i64, (lon * 1e5).round() as i64);
+        encode_polyline_value(current.0 - previous.0, &mut result);
+        encode_polyline_value(current.1 - previous.1, &mut result);
+        previous = current;
+    }
+    result
+}
+
+fn encode_polyline_value(value: i64, output: &mut String) {
+    let mut value = if value < 0 { !(value << 1) } else { value << 1 };
+    while value >= 0x20 {
+        output.push((((0x20 | (value & 0x1f)) + 63) as u8) as char);
+        value >>= 5;
+    }
+    output.push(((value + 63) as u8) as char);
+}
+
+/// Decodes a Google encoded polyline into (latitude, longitude) points
+pub fn decode_polyline(polyline: &str) -> Result<Vec<(f64, f64)>, GtfsError> {
+    let mut bytes = polyline.bytes();
+    let mut points = Vec::new();
+    let (mut lat, mut lon) = (0, 0);
+    loop {
+        let delta_lat = match decode_polyline_value(&mut bytes)
+            .map_err(|_| GtfsError::InvalidPolyline(polyline.to_owned()))?
+        {
+            Some(value) => value,
+            None => break,
+        };
+        let delta_lon = decode_polyline_value(&mut bytes)
+            .map_err(|_| GtfsError::InvalidPolyline(polyline.to_owned()))?
+            .ok_or_else(|| GtfsError::InvalidPolyline(polyline.to_owned()))?;
+        lat += delta_lat;
+        lon += delta_lon;
+        points.push((lat as f64 / 1e5, lon as f64 / 1e5));
+    }
+    Ok(points)
+}
+
+fn decode_polyline_value<I: Iterator<Item = u8>>(bytes: &mut I) -> Result<Option<i64>, ()> {
+    let mut result = 0i64;
+    let mut shift = 0;
+    let mut started = false;
+    for byte in bytes {
+        if !(63..=127).contains(&byte) || shift > 60 {
+            return Err(());
+        }
+        started = true;
+        let chunk = i64::from(byte - 63);
+        result |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk < 0x20 {
+            let value = if result & 1 == 1 {
+                !(result >> 1)
+            } else {
+                result >> 1
+            };
+            return Ok(Some(value));
+        }
+    }
+    if started {
+        Err(())
+    } else {
+        Ok(None)
+    }
+}
+
+fn interpolate_trip_stop_times(stop_times: &mut [StopTime]) {
+    for stop_time in stop_times.iter_mut() {
+        if stop_time.arrival_time.is_none() {
+            stop_time.arrival_time = stop_time.departure_time;
+        }
+        if stop_time.departure_time.is_none() {
+            stop_time.departure_time = stop_time.arrival_time;
+        }
+    }
+
+    let known: Vec<usize> = stop_times
+        .iter()
+        .enumerate()
+        .filter(|(_, st)| st.departure_time.is_some())
+        .map(|(i, _)| i)
+        .collect();
+
+    for window in known.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        if end - start < 2 {
+            continue;
+        }
+        let start_time = stop_times[start].departure_time.unwrap_or(0);
+        let end_time = stop_times[end].arrival_time.unwrap_or(start_time);
+        let distances = stop_times[start..=end]
+            .iter()
+            .map(|st| st.shape_dist_traveled)
+            .collect::<Option<Vec<f32>>>()
+            // Distances that do not increase would put the stops outside of the known times
+            .filter(|d| d[d.len() - 1] > d[0] && d.windows(2).all(|w| w[0] <= w[1]));
+
+        for i in start + 1..end {
+            let ratio = match &distances {
+                Some(d) => f64::from(d[i - start] - d[0]) / f64::from(d[d.len() - 1] - d[0]),
+                None => (i - start) as f64 / (end - start) as f64,
+            }
+            .clamp(0.0, 1.0);
+            let time =
+                f64::from(start_time) + ratio * (f64::from(end_time) - f64::from(start_time));
+            let time = Some(time.round() as u32);
+            stop_times[i].arrival_time = time;
+            stop_times[i].departure_time = time;
+        }
+    }
+}
+
+fn deserialize_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let s = s.trim();
+    Ok(s == "1" || s.eq_ignore_ascii_case("true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_calendar() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_calendars(File::open("fixtures/calendar.txt").unwrap())
+            .unwrap();
+        assert_eq!(1, gtfs.calendar.len());
+        assert!(!gtfs.calendar["service1"].monday);
+        assert!(gtfs.calendar["service1"].saturday);
+    }
+
+    #[test]
+    fn read_calendar_with_loose_booleans() {
+        let mut gtfs = Gtfs::default();
+        let data = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date
+service1, 1 ,0,,true,false,1,0,20170101,20170115
+";
+        gtfs.read_calendars(data.as_bytes()).unwrap();
+        let calendar = &gtfs.calendar["service1"];
+        assert!(calendar.monday);
+        assert!(!calendar.tuesday);
+        assert!(!calendar.wednesday);
+        assert!(calendar.thursday);
+        assert!(!calendar.friday);
+    }
+
+    fn calendar(days: [bool; 7]) -> Calendar {
+        Calendar {
+            id: "service".to_owned(),
+            monday: days[0],
+            tuesday: days[1],
+            wednesday: days[2],
+            thursday: days[3],
+            friday: days[4],
+            saturday: days[5],
+            sunday: days[6],
+            start_date: NaiveDate::from_ymd_opt(2017, 1, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2017, 1, 15).unwrap(),
+        }
+    }
+
+    #[test]
+    fn active_weekdays() {
+        let weekdays = calendar([true, false, true, false, false, false, true]);
+        assert_eq!(
+            vec![Weekday::Mon, Weekday::Wed, Weekday::Sun],
+            weekdays.active_weekdays()
+        );
+        assert!(!weekdays.runs_every_day());
+        assert!(calendar([true; 7]).runs_every_day());
+        assert!(calendar([false; 7]).active_weekdays().is_empty());
+    }
+
+    #[test]
+    fn weekday_summary() {
+        let summary = |days| calendar(days).weekday_summary();
+        assert_eq!(
+            "Monday–Friday",
+            summary([true, true, true, true, true, false, false])
+        );
+        assert_eq!(
+            "Weekends",
+            summary([false, false, false, false, false, true, true])
+        );
+        assert_eq!("Daily", summary([true; 7]));
+        assert_eq!("No weekly service", summary([false; 7]));
+        assert_eq!(
+            "Monday, Wednesday",
+            summary([true, false, true, false, false, false, false])
+        );
+        assert_eq!(
+            "Tuesday",
+            summary([false, true, false, false, false, false, false])
+        );
+    }
+
+    #[test]
+    fn service_summary() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            "Weekends (with exceptions)",
+            gtfs.service_summary("service1")
+        );
+        assert_eq!(
+            "No weekly service (with exceptions)",
+            gtfs.service_summary("service2")
+        );
+        gtfs.calendar_dates.clear();
+        gtfs.calendar.insert(
+            "weekdays".to_owned(),
+            calendar([true, true, true, true, true, false, false]),
+        );
+        assert_eq!("Monday–Friday", gtfs.service_summary("weekdays"));
+    }
+
+    #[test]
+    fn read_calendar_dates() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_calendar_dates(File::open("fixtures/calendar_dates.txt").unwrap())
+            .unwrap();
+        assert_eq!(2, gtfs.calendar_dates.len());
+        assert_eq!(2, gtfs.calendar_dates["service1"].len());
+        assert_eq!(2, gtfs.calendar_dates["service1"][0].exception_type);
+        assert_eq!(1, gtfs.calendar_dates["service2"][0].exception_type);
+    }
+
+    #[test]
+    fn read_stop() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        assert_eq!(5, gtfs.stops.len());
+        assert_eq!(
+            LocationType::StopArea,
+            gtfs.get_stop("stop1").unwrap().location_type
+        );
+        assert_eq!(
+            LocationType::StopPoint,
+            gtfs.get_stop("stop2").unwrap().location_type
+        );
+        assert_eq!(
+            Some("1".to_owned()),
+            gtfs.get_stop("stop3").unwrap().parent_station
+        );
+    }
+
+    #[test]
+    fn read_stop_with_quoted_fields() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops_quoted.txt").unwrap())
+            .unwrap();
+        assert_eq!(2, gtfs.stops.len());
+        let stop = gtfs.get_stop("stop1").unwrap();
+        assert_eq!("Main St, Downtown", stop.name);
+        assert_eq!("Near the \"big\"\nfountain", stop.description);
+    }
+
+    #[test]
+    fn read_padded_values() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops_padded.txt").unwrap())
+            .unwrap();
+        let station = gtfs.get_stop("station1").unwrap();
+        assert_eq!("Station", station.name);
+        assert_eq!(48.796058, station.latitude);
+        assert_eq!(LocationType::StopArea, station.location_type);
+        assert_eq!(Availability::Available, station.wheelchair_boarding);
+        let child = gtfs.get_stop("child1").unwrap();
+        assert_eq!("Platform 1", child.name);
+        assert_eq!(2.449386, child.longitude);
+        assert_eq!(Some("station1".to_owned()), child.parent_station);
+    }
+
+    #[test]
+    fn read_stops_long_coordinate_names() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops_long_names.txt").unwrap())
+            .unwrap();
+        let stop = gtfs.get_stop("stop1").unwrap();
+        assert_eq!(48.8447, stop.latitude);
+        assert_eq!(2.3739, stop.longitude);
+
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        assert_eq!(48.796058, gtfs.get_stop("stop2").unwrap().latitude);
+    }
+
+    #[test]
+    fn read_padded_ids() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_padded_ids.txt").unwrap(),
+            )
+            .unwrap();
+        let stops: Vec<&str> = gtfs.trips["trip1"]
+            .stop_times
+            .iter()
+            .map(|st| st.stop.id.as_str())
+            .collect();
+        assert_eq!(vec!["stop2", "stop3"], stops);
+    }
+
+    #[test]
+    fn read_decimal_comma() {
+        let mut gtfs = Gtfs::default();
+        assert!(gtfs
+            .read_stops(File::open("fixtures/stops_decimal_comma.txt").unwrap())
+            .is_err());
+        GtfsReader::default()
+            .decimal_comma(true)
+            .read_file(
+                &mut gtfs,
+                "stops.txt",
+                File::open("fixtures/stops_decimal_comma.txt").unwrap(),
+            )
+            .unwrap();
+        let stop = gtfs.get_stop("stop1").unwrap();
+        assert_eq!(38.707751, stop.latitude);
+        assert_eq!(-9.136592, stop.longitude);
+        assert_eq!("Praça do Comércio", stop.name);
+        assert_eq!(38.713909, gtfs.get_stop("stop2").unwrap().latitude);
+    }
+
+    #[test]
+    fn get_stop_ci() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert!(gtfs.get_stop("STOP1").is_err());
+        assert_eq!("stop1", gtfs.get_stop_ci("STOP1").unwrap().id);
+        assert_eq!("trip1", gtfs.get_trip_ci("Trip1").unwrap().id);
+        assert_eq!(
+            "invalid_type",
+            gtfs.get_route_ci("INVALID_TYPE").unwrap().id
+        );
+        assert_eq!("service1", gtfs.get_calendar_ci("SERVICE1").unwrap().id);
+        assert_eq!("Utopia", gtfs.get_stop_ci("Utopia").unwrap_err().id);
+
+        // A stop time referencing a stop with another casing than in stops.txt
+        let mut stop_ids = Vec::new();
+        Gtfs::for_each_stop_time(
+            File::open("fixtures/stop_times_upper_case.txt").unwrap(),
+            |row| stop_ids.push(row.stop_id),
+        )
+        .unwrap();
+        assert_eq!("STOP1", stop_ids[0]);
+        assert!(gtfs.get_stop(&stop_ids[0]).is_err());
+        assert_eq!("stop1", gtfs.get_stop_ci(&stop_ids[0]).unwrap().id);
+    }
+
+    #[test]
+    fn effective_wheelchair_boarding() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops_wheelchair.txt").unwrap())
+            .unwrap();
+        assert_eq!(
+            Availability::Available,
+            gtfs.effective_wheelchair_boarding("child1")
+        );
+        assert_eq!(
+            Availability::NotAvailable,
+            gtfs.effective_wheelchair_boarding("child2")
+        );
+        assert_eq!(
+            Availability::InformationNotAvailable,
+            gtfs.effective_wheelchair_boarding("orphan")
+        );
+        assert_eq!(
+            Availability::InformationNotAvailable,
+            gtfs.effective_wheelchair_boarding("unknown")
+        );
+    }
+
+    #[test]
+    fn read_routes() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes.txt").unwrap())
+            .unwrap();
+        assert_eq!(2, gtfs.routes.len());
+        assert_eq!(RouteType::Bus, gtfs.get_route("1").unwrap().route_type);
+        assert_eq!(RouteType::Other(42), gtfs.get_route("invalid_type").unwrap().route_type);
+    }
+
+    #[test]
+    fn error_on_unknown_route_type() {
+        let gtfs = GtfsReader::default().read("fixtures/").unwrap();
+        assert_eq!(
+            RouteType::Other(42),
+            gtfs.get_route("invalid_type").unwrap().route_type
+        );
+
+        let err = GtfsReader::default()
+            .error_on_unknown_route_type(true)
+            .read("fixtures/")
+            .err()
+            .unwrap();
+        match err {
+            GtfsError::InvalidRouteType {
+                route_id,
+                route_type,
+            } => {
+                assert_eq!("invalid_type", route_id);
+                assert_eq!(42, route_type);
+            }
+            err => panic!("unexpected error {}", err),
+        }
+        assert!(RouteType::Other(700).is_known());
+        // The option only applies to the reader that set it
+        assert!(Gtfs::new("fixtures/").is_ok());
+    }
+
+    #[test]
+    fn read_route_sort_order() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes_sort_order.txt").unwrap())
+            .unwrap();
+        assert_eq!(Some(2), gtfs.get_route("A").unwrap().route_order);
+        assert_eq!(Some(1), gtfs.get_route("B").unwrap().route_order);
+
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes_order.txt").unwrap())
+            .unwrap();
+        assert_eq!(Some(2), gtfs.get_route("A").unwrap().route_order);
+    }
+
+    #[test]
+    fn read_route_colors() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let route = gtfs.get_route("1").unwrap();
+        assert_eq!(Color::BLACK, route.color);
+        assert_eq!(Color::WHITE, route.text_color);
+        assert_eq!("FFFFFF", route.text_color.to_string());
+        assert_eq!(
+            Color {
+                r: 0x12,
+                g: 0xab,
+                b: 0xEF
+            },
+            "12abEF".parse().unwrap()
+        );
+        assert!("12345".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn read_invalid_route_colors() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes_invalid_colors.txt").unwrap())
+            .unwrap();
+        assert_eq!(Color::WHITE, gtfs.get_route("short").unwrap().color);
+        let route = gtfs.get_route("hash").unwrap();
+        assert_eq!(Color::WHITE, route.color);
+        assert_eq!(Color::BLACK, route.text_color);
+        assert_eq!(
+            Color { r: 0, g: 0, b: 255 },
+            gtfs.get_route("valid").unwrap().color
+        );
+        assert_eq!(3, gtfs.warnings.len());
+
+        let err = GtfsReader::default()
+            .error_on_invalid_color(true)
+            .read_file(
+                &mut Gtfs::default(),
+                "routes.txt",
+                File::open("fixtures/routes_invalid_colors.txt").unwrap(),
+            )
+            .unwrap_err();
+        match err {
+            GtfsError::InvalidColor(color) => assert_eq!("FFF", color),
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn validate_continuous_consistency() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes_continuous.txt").unwrap())
+            .unwrap();
+        gtfs.read_trips(File::open("fixtures/trips_continuous.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_continuous.txt").unwrap(),
+            )
+            .unwrap();
+        let route = gtfs.get_route("regular").unwrap();
+        assert_eq!(
+            ContinuousPickupDropOff::NotAvailable,
+            route.continuous_pickup
+        );
+        assert_eq!(
+            ContinuousPickupDropOff::Continuous,
+            gtfs.get_route("continuous").unwrap().continuous_drop_off
+        );
+        assert_eq!(
+            Some(ContinuousPickupDropOff::ArrangeByPhone),
+            gtfs.get_trip("stop_time_continuous").unwrap().stop_times[0].continuous_drop_off
+        );
+        assert_eq!(
+            vec!["continuous_with_pickup_type", "stop_time_continuous"],
+            gtfs.validate_continuous_consistency()
+        );
+    }
+
+    #[test]
+    fn validate_color_contrast() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes_low_contrast.txt").unwrap())
+            .unwrap();
+        let route = gtfs.get_route("default_colors").unwrap();
+        assert_eq!(Color::WHITE, route.color);
+        assert_eq!(Color::BLACK, route.text_color);
+        assert!((Color::WHITE.contrast_ratio(Color::BLACK) - 21.0).abs() < 1e-9);
+        assert_eq!(vec!["low_contrast"], gtfs.validate_color_contrast());
+    }
+
+    #[test]
+    fn find_duplicate_routes() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes_duplicated.txt").unwrap())
+            .unwrap();
+        assert_eq!(
+            vec![vec!["A".to_owned(), "A_copy".to_owned()]],
+            gtfs.find_duplicate_routes()
+        );
+        assert!(Gtfs::new("fixtures/")
+            .unwrap()
+            .find_duplicate_routes()
+            .is_empty());
+    }
+
+    #[test]
+    fn routes_in_network() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_routes(File::open("fixtures/routes.txt").unwrap())
+            .unwrap();
+        assert_eq!(
+            Some("urban".to_owned()),
+            gtfs.get_route("1").unwrap().network_id
+        );
+        assert_eq!(None, gtfs.get_route("invalid_type").unwrap().network_id);
+        let routes = gtfs.routes_in_network("urban");
+        assert_eq!(1, routes.len());
+        assert_eq!("1", routes[0].id);
+        assert!(gtfs.routes_in_network("suburban").is_empty());
+    }
+
+    #[test]
+    fn route_type_groups() {
+        for rail in &[
+            RouteType::Tramway,
+            RouteType::Subway,
+            RouteType::Rail,
+            RouteType::CableCar,
+            RouteType::Funicular,
+            RouteType::Other(102),
+            RouteType::Other(401),
+        ] {
+            assert!(rail.is_rail_like());
+            assert!(!rail.is_bus_like());
+            assert!(!rail.is_water());
+        }
+        for bus in &[RouteType::Bus, RouteType::Other(11), RouteType::Other(702)] {
+            assert!(bus.is_bus_like());
+            assert!(!bus.is_rail_like());
+            assert!(!bus.is_water());
+        }
+        for water in &[RouteType::Ferry, RouteType::Other(1000)] {
+            assert!(water.is_water());
+            assert!(!water.is_rail_like());
+            assert!(!water.is_bus_like());
+        }
+        for other in &[RouteType::Gondola, RouteType::Other(42)] {
+            assert!(!other.is_rail_like());
+            assert!(!other.is_bus_like());
+            assert!(!other.is_water());
+        }
+    }
+
+    #[test]
+    fn read_trips() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        assert_eq!(1, gtfs.trips.len());
+    }
+
+    #[test]
+    fn read_stop_times() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        gtfs.read_stop_times(File::open("fixtures/stop_times.txt").unwrap())
+            .unwrap();
+        let stop_times = &gtfs.trips.get("trip1").unwrap().stop_times;
+        assert_eq!(2, stop_times.len());
+        assert_eq!(
+            PickupDropOffType::Regular,
+            stop_times[0].pickup_type.unwrap()
+        );
+        assert_eq!(
+            PickupDropOffType::NotAvailable,
+            stop_times[0].drop_off_type.unwrap()
+        );
+        assert_eq!(
+            PickupDropOffType::ArrangeByPhone,
+            stop_times[1].pickup_type.unwrap()
+        );
+        assert_eq!(None, stop_times[1].drop_off_type);
+    }
+
+    #[test]
+    fn clone_trip() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let trip = gtfs.get_trip("trip1").unwrap();
+        let cloned = trip.clone();
+        assert_eq!(trip, &cloned);
+        assert_eq!(2, cloned.stop_times.len());
+    }
+
+    #[test]
+    fn read_unsorted_stop_times() {
+        let data = "trip_id,arrival_time,departure_time,stop_id,stop_sequence
+trip1,10:10:00,10:10:00,stop2,2
+trip1,10:00:00,10:00:00,stop1,1
+";
+        let sequences = |sort| {
+            let mut gtfs = Gtfs::default();
+            gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+                .unwrap();
+            gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+                .unwrap();
+            GtfsReader::default()
+                .sort_stop_times(sort)
+                .read_stop_times_file(&mut gtfs, data.as_bytes())
+                .unwrap();
+            gtfs.trips["trip1"]
+                .stop_times
+                .iter()
+                .map(|st| st.stop_sequence)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![1, 2], sequences(true));
+        assert_eq!(vec![2, 1], sequences(false));
+    }
+
+    #[test]
+    fn for_each_stop_time() {
+        let mut count = 0;
+        let mut stops = HashSet::new();
+        Gtfs::for_each_stop_time(
+            File::open("fixtures/stop_times_unordered.txt").unwrap(),
+            |row| {
+                count += 1;
+                assert_eq!("trip1", row.trip_id);
+                stops.insert(row.stop_id);
+            },
+        )
+        .unwrap();
+        assert_eq!(4, count);
+        assert_eq!(4, stops.len());
+    }
+
+    #[test]
+    fn trips_by_short_name() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips_named.txt").unwrap())
+            .unwrap();
+        let ids = |name| {
+            gtfs.trips_by_short_name(name)
+                .iter()
+                .map(|trip| trip.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["trip_a", "trip_b"], ids("8452"));
+        assert_eq!(vec!["trip_c"], ids("8453"));
+        assert!(ids("").is_empty());
+        assert_eq!(None, gtfs.get_trip("trip_d").unwrap().trip_short_name);
+    }
+
+    #[test]
+    fn trip_patterns() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let mut other = gtfs.trips["trip1"].clone();
+        other.id = "trip2".to_owned();
+        gtfs.trips.insert(other.id.to_owned(), other);
+        let mut short = gtfs.trips["trip1"].clone();
+        short.id = "short".to_owned();
+        short.stop_times.pop();
+        gtfs.trips.insert(short.id.to_owned(), short);
+
+        let patterns = gtfs.trip_patterns();
+        assert_eq!(2, patterns.len());
+        assert_eq!(
+            vec!["trip1", "trip2"],
+            patterns[&vec!["stop2".to_owned(), "stop3".to_owned()]]
+        );
+        assert_eq!(vec!["short"], patterns[&vec!["stop2".to_owned()]]);
+    }
+
+    #[test]
+    fn trips_in_block() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips_block.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_block.txt").unwrap(),
+            )
+            .unwrap();
+        let trips: Vec<&str> = gtfs
+            .trips_in_block("block1")
+            .iter()
+            .map(|trip| trip.id.as_str())
+            .collect();
+        assert_eq!(vec!["outbound", "inbound"], trips);
+        assert_eq!(None, gtfs.get_trip("unblocked").unwrap().block_id);
+        assert!(gtfs.trips_in_block("unknown").is_empty());
+    }
+
+    #[test]
+    fn trips_missing_shapes() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips_shapes.txt").unwrap())
+            .unwrap();
+        gtfs.read_shapes(File::open("fixtures/shapes.txt").unwrap())
+            .unwrap();
+        let ids: Vec<&str> = gtfs
+            .trips_missing_shapes()
+            .iter()
+            .map(|trip| trip.id.as_str())
+            .collect();
+        assert_eq!(vec!["unknown_shape", "without_shape"], ids);
+    }
+
+    #[test]
+    fn duplicate_shape_points() {
+        let read = |reader: GtfsReader| {
+            let mut gtfs = Gtfs::default();
+            reader
+                .read_file(
+                    &mut gtfs,
+                    "shapes.txt",
+                    File::open("fixtures/shapes_duplicate_sequence.txt").unwrap(),
+                )
+                .map(|_| gtfs)
+        };
+        let mut gtfs = Gtfs::default();
+        gtfs.read_shapes(File::open("fixtures/shapes_duplicate_sequence.txt").unwrap())
+            .unwrap();
+        assert_eq!(4, gtfs.shapes["shape1"].len());
+
+        match read(GtfsReader::default().error_on_duplicate_shape_points(true)) {
+            Err(GtfsError::DuplicateShapePoint { shape_id, sequence }) => {
+                assert_eq!("shape1", shape_id);
+                assert_eq!(2, sequence);
+            }
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("duplicate shape points should fail"),
+        }
+
+        let gtfs = read(GtfsReader::default()).unwrap();
+        let shape = &gtfs.shapes["shape1"];
+        assert_eq!(
+            vec![1, 2, 3],
+            shape.iter().map(|p| p.sequence).collect::<Vec<_>>()
+        );
+        assert_eq!(48.796058, shape[1].latitude);
+        assert_eq!(1, gtfs.warnings.len());
+    }
+
+    #[test]
+    fn normalize_shape_distances() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        gtfs.shapes.clear();
+        gtfs.read_shapes(File::open("fixtures/shapes_km.txt").unwrap())
+            .unwrap();
+        gtfs.trips.get_mut("trip1").unwrap().stop_times[1].shape_dist_traveled = Some(1.5);
+        gtfs.normalize_shape_distances(DistanceUnit::Kilometers);
+        let distances: Vec<Option<f32>> = gtfs.shapes["shape1"]
+            .iter()
+            .map(|point| point.dist_traveled)
+            .collect();
+        assert_eq!(vec![Some(0.0), Some(250.0), None], distances);
+        let trip = gtfs.get_trip("trip1").unwrap();
+        assert_eq!(None, trip.stop_times[0].shape_dist_traveled);
+        assert_eq!(Some(1500.0), trip.stop_times[1].shape_dist_traveled);
+    }
+
+    #[test]
+    fn language_tag() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/agency_lang.txt").unwrap())
+            .unwrap();
+        let tag = gtfs.agencies[1].language_tag().unwrap();
+        assert_eq!("en-US", tag.to_string());
+        assert_eq!("en", tag.primary_language());
+        assert_eq!("fr", gtfs.agencies[0].language_tag().unwrap().to_string());
+        assert_eq!(None, gtfs.agencies[2].language_tag());
+        assert_eq!(None, gtfs.agencies[3].language_tag());
+        assert!("e".parse::<LanguageTag>().is_err());
+        assert!("en-".parse::<LanguageTag>().is_err());
+        assert_eq!(
+            vec!["Invalid agency_lang fr_FR for the agency Ferries"],
+            gtfs.validate_languages()
+        );
+    }
+
+    #[test]
+    fn routes_for_agency() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/agency_two.txt").unwrap())
+            .unwrap();
+        gtfs.read_routes(File::open("fixtures/routes_agencies.txt").unwrap())
+            .unwrap();
+        let ids = |gtfs: &Gtfs, agency_id| -> Vec<String> {
+            gtfs.routes_for_agency(agency_id)
+                .iter()
+                .map(|route| route.id.to_owned())
+                .collect()
+        };
+        assert_eq!(vec!["b1", "b2"], ids(&gtfs, "bus"));
+        assert_eq!(vec!["r1"], ids(&gtfs, "rail"));
+        assert!(ids(&gtfs, "unknown").is_empty());
+
+        gtfs.agencies.truncate(1);
+        assert_eq!(vec!["b1", "b2", "unassigned"], ids(&gtfs, "bus"));
+        assert_eq!(vec!["r1"], ids(&gtfs, "rail"));
+    }
+
+    #[test]
+    fn get_agency() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/padded_agency_ids/agency.txt").unwrap())
+            .unwrap();
+        gtfs.read_routes(File::open("fixtures/padded_agency_ids/routes.txt").unwrap())
+            .unwrap();
+        for (route_id, name) in &[("b1", "City Bus"), ("r1", "Regional Rail")] {
+            assert_eq!(*name, gtfs.route_agency(route_id).unwrap().name);
+        }
+
+        gtfs.agencies[0].id = Some("1 ".to_owned());
+        gtfs.routes.get_mut("r1").unwrap().agency_id = Some(" 2 ".to_owned());
+        assert_eq!("City Bus", gtfs.get_agency(" 1").unwrap().name);
+        assert_eq!("City Bus", gtfs.route_agency("b1").unwrap().name);
+        assert_eq!("Regional Rail", gtfs.route_agency("r1").unwrap().name);
+        assert!(gtfs.get_agency("3").is_err());
+        let route_ids = |agency_id| -> Vec<&str> {
+            gtfs.routes_for_agency(agency_id)
+                .iter()
+                .map(|route| route.id.as_str())
+                .collect()
+        };
+        assert_eq!(vec!["b1"], route_ids(" 1"));
+        assert_eq!(vec!["r1"], route_ids("2"));
+    }
+
+    #[test]
+    fn trip_context() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/agency_two.txt").unwrap())
+            .unwrap();
+        gtfs.read_routes(File::open("fixtures/routes_agencies.txt").unwrap())
+            .unwrap();
+        for (trip_id, route_id) in &[
+            ("rail_trip", "r1"),
+            ("shuttle", "unassigned"),
+            ("lost", "x"),
+        ] {
+            gtfs.trips.insert(
+                trip_id.to_string(),
+                Trip {
+                    id: trip_id.to_string(),
+                    route_id: route_id.to_string(),
+                    ..Trip::default()
+                },
+            );
+        }
+
+        let (trip, route, agency) = gtfs.trip_context("rail_trip").unwrap();
+        assert_eq!("rail_trip", trip.id);
+        assert_eq!("Coast line", route.long_name);
+        assert_eq!("Regional Rail", agency.unwrap().name);
+        assert!(gtfs.trip_context("shuttle").unwrap().2.is_none());
+        assert!(gtfs.trip_context("lost").is_none());
+        assert!(gtfs.trip_context("unknown").is_none());
+
+        gtfs.agencies.truncate(1);
+        let agency = gtfs.trip_context("shuttle").unwrap().2;
+        assert_eq!("City Bus", agency.unwrap().name);
+    }
+
+    #[test]
+    fn stop_time_agency() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/agency_two.txt").unwrap())
+            .unwrap();
+        gtfs.read_routes(File::open("fixtures/routes_agencies.txt").unwrap())
+            .unwrap();
+        for (trip_id, route_id) in &[("rail_trip", "r1"), ("shuttle", "unassigned")] {
+            gtfs.trips.insert(
+                trip_id.to_string(),
+                Trip {
+                    id: trip_id.to_string(),
+                    route_id: route_id.to_string(),
+                    stop_times: vec![StopTime {
+                        stop_sequence: 1,
+                        ..StopTime::default()
+                    }],
+                    ..Trip::default()
+                },
+            );
+        }
+        assert_eq!(
+            "Regional Rail",
+            gtfs.stop_time_agency("rail_trip", 1).unwrap().name
+        );
+        assert!(gtfs.stop_time_agency("rail_trip", 2).is_none());
+        assert!(gtfs.stop_time_agency("shuttle", 1).is_none());
+        assert!(gtfs.stop_time_agency("unknown", 1).is_none());
+    }
+
+    #[test]
+    fn representative_trip() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips_block.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_block.txt").unwrap(),
+            )
+            .unwrap();
+        assert_eq!("inbound", gtfs.representative_trip("route1").unwrap().id);
+        let (first, last) = gtfs.route_terminals("route1").unwrap();
+        assert_eq!(("stop2", "stop1"), (first.id.as_str(), last.id.as_str()));
+
+        gtfs.read_shapes(File::open("fixtures/shapes.txt").unwrap())
+            .unwrap();
+        gtfs.trips.get_mut("outbound").unwrap().shape_id = Some("shape1".to_owned());
+        assert_eq!("outbound", gtfs.representative_trip("route1").unwrap().id);
+
+        assert!(gtfs.representative_trip("unknown").is_none());
+        assert!(gtfs.route_terminals("unknown").is_none());
+    }
+
+    #[test]
+    fn validate_time_bounds() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_absurd.txt").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(vec![("trip1".to_owned(), 2)], gtfs.validate_time_bounds());
+    }
+
+    #[test]
+    fn validate_block_overlaps() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips_block_overlap.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_block_overlap.txt").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![("morning".to_owned(), "late_return".to_owned())],
+            gtfs.validate_block_overlaps()
+        );
+    }
+
+    #[test]
+    fn trips_between_stops() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_unordered.txt").unwrap(),
+            )
+            .unwrap();
+        let trips = gtfs.trips_between_stops("stop1", "stop3");
+        assert_eq!(1, trips.len());
+        assert_eq!("trip1", trips[0].id);
+        assert!(gtfs.trips_between_stops("stop3", "stop1").is_empty());
+        assert!(gtfs.trips_between_stops("stop1", "unknown").is_empty());
+    }
+
+    #[test]
+    fn stop_time_at_sequence() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_unordered.txt").unwrap(),
+            )
+            .unwrap();
+        let trip = gtfs.get_trip("trip1").unwrap();
+        assert_eq!("stop3", trip.stop_time_at_sequence(3).unwrap().stop.id);
+        assert_eq!("stop1", trip.stop_time_at_sequence(1).unwrap().stop.id);
+        assert!(trip.stop_time_at_sequence(5).is_none());
+    }
+
+    #[test]
+    fn trip_stop_times_csv() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let csv = gtfs.trip_stop_times_csv("trip1").unwrap();
+        assert!(csv.starts_with("trip_id,arrival_time,departure_time,stop_id,stop_sequence,"));
+        assert!(csv.contains("trip1,14:00:00,14:00:00,stop2,0,0,1,"));
+        assert_eq!(None, gtfs.trip_stop_times_csv("unknown"));
+
+        let mut copy = Gtfs::default();
+        copy.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        copy.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(&mut copy, csv.as_bytes())
+            .unwrap();
+        assert_eq!(
+            gtfs.trips["trip1"].stop_times,
+            copy.trips["trip1"].stop_times
+        );
+    }
+
+    #[test]
+    fn read_stop_times_with_missing_stop() {
+        let read = |on_missing_stop| {
+            let mut gtfs = Gtfs::default();
+            gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+                .unwrap();
+            gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+                .unwrap();
+            GtfsReader::default()
+                .on_missing_stop(on_missing_stop)
+                .read_stop_times_file(
+                    &mut gtfs,
+                    File::open("fixtures/stop_times_missing_stop.txt").unwrap(),
+                )
+                .map(|_| gtfs)
+        };
+
+        let err = read(OnMissing::Error).err().unwrap();
+        match err {
+            GtfsError::Reference(e) => assert_eq!("unknown_stop", e.id),
+            _ => panic!("unexpected error {}", err),
+        }
+
+        let gtfs = read(OnMissing::Skip).unwrap();
+        assert_eq!(2, gtfs.trips["trip1"].stop_times.len());
+        assert_eq!(1, gtfs.warnings.len());
+        assert!(gtfs.get_stop("unknown_stop").is_err());
+
+        let gtfs = read(OnMissing::Placeholder).unwrap();
+        assert_eq!(3, gtfs.trips["trip1"].stop_times.len());
+        assert!(gtfs.warnings.is_empty());
+        assert_eq!("unknown_stop", gtfs.trips["trip1"].stop_times[1].stop.id);
+        assert_eq!("", gtfs.get_stop("unknown_stop").unwrap().name);
+    }
+
+    #[test]
+    fn read_stop_times_before_trips() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        let err = GtfsReader::default()
+            .read_stop_times_file(&mut gtfs, File::open("fixtures/stop_times.txt").unwrap())
+            .unwrap_err();
+        match err {
+            GtfsError::StopTime(StopTimeError::TripNotLoaded { ref trip_id }) => {
+                assert_eq!("trip1", trip_id)
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn interpolate_stop_times() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        let data = "trip_id,arrival_time,departure_time,stop_id,stop_sequence
+trip1,10:00:00,10:00:00,stop1,1
+trip1,,,stop2,2
+trip1,,,stop3,3
+trip1,10:30:00,10:30:00,stop4,4
+";
+        GtfsReader::default()
+            .read_stop_times_file(&mut gtfs, data.as_bytes())
+            .unwrap();
+        assert_eq!(None, gtfs.trips["trip1"].stop_times[1].arrival_time);
+
+        gtfs.interpolate_stop_times();
+        let stop_times = &gtfs.trips["trip1"].stop_times;
+        assert_eq!(Some(10 * 3600 + 10 * 60), stop_times[1].arrival_time);
+        assert_eq!(Some(10 * 3600 + 20 * 60), stop_times[2].departure_time);
+    }
+
+    #[test]
+    fn interpolate_stop_times_with_decreasing_distances() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        let data = "trip_id,arrival_time,departure_time,stop_id,stop_sequence,shape_dist_traveled
+trip1,10:00:00,10:00:00,stop1,1,0
+trip1,,,stop2,2,500
+trip1,,,stop3,3,200
+trip1,10:30:00,10:30:00,stop4,4,300
+";
+        GtfsReader::default()
+            .read_stop_times_file(&mut gtfs, data.as_bytes())
+            .unwrap();
+
+        gtfs.interpolate_stop_times();
+        let stop_times = &gtfs.trips["trip1"].stop_times;
+        assert_eq!(Some(10 * 3600 + 10 * 60), stop_times[1].arrival_time);
+        assert_eq!(Some(10 * 3600 + 20 * 60), stop_times[2].departure_time);
+    }
+
+    #[test]
+    fn read_shapes() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_shapes(File::open("fixtures/shapes.txt").unwrap())
+            .unwrap();
+        let shape = gtfs.get_shape("shape1").unwrap();
+        assert_eq!(3, shape.len());
+        assert_eq!(1, shape[0].sequence);
+        assert_eq!(Some(100.0), shape[1].dist_traveled);
+        assert_eq!(None, shape[2].dist_traveled);
+    }
+
+    #[test]
+    fn polyline() {
+        let points = vec![(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)];
+        let encoded = encode_polyline(&points);
+        assert_eq!("_p~iF~ps|U_ulLnnqC_mqNvxq`@", encoded);
+        assert_eq!(points, decode_polyline(&encoded).unwrap());
+        assert!(decode_polyline("_p~iF").is_err());
+
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let shape = gtfs.shape_polyline("shape1").unwrap();
+        assert_eq!((48.795058, 2.448386), shape[0]);
+        assert_eq!(3, shape.len());
+        assert_eq!(None, gtfs.shape_polyline("unknown"));
+    }
+
+    #[test]
+    fn trip_geometry() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let trip = gtfs.trips.get_mut("trip1").unwrap();
+        trip.stop_times[1].stop = Arc::new(Stop::default());
+        assert_eq!(
+            Some(vec![(48.796058, 2.449386)]),
+            gtfs.trip_geometry("trip1")
+        );
+
+        gtfs.trips.get_mut("trip1").unwrap().shape_id = Some("shape1".to_owned());
+        assert_eq!(gtfs.shape_polyline("shape1"), gtfs.trip_geometry("trip1"));
+        assert_eq!(3, gtfs.trip_geometry("trip1").unwrap().len());
+
+        gtfs.shapes.clear();
+        assert_eq!(1, gtfs.trip_geometry("trip1").unwrap().len());
+        assert_eq!(None, gtfs.trip_geometry("unknown"));
+    }
+
+    #[test]
+    fn trip_distance() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let stop_distance = gtfs.trip_distance("trip1").unwrap();
+        assert!(stop_distance.abs() < 1e-6);
+
+        let trip = gtfs.trips.get_mut("trip1").unwrap();
+        trip.stop_times[1].stop = Arc::new(Stop {
+            latitude: 48.806058,
+            longitude: 2.449386,
+            ..Stop::default()
+        });
+        let stop_distance = gtfs.trip_distance("trip1").unwrap();
+        assert!((stop_distance - 1112.0).abs() < 1.0);
+
+        gtfs.trips.get_mut("trip1").unwrap().shape_id = Some("shape1".to_owned());
+        let shape_distance = gtfs.trip_distance("trip1").unwrap();
+        assert!((shape_distance - 266.0).abs() < 1.0);
+        assert_eq!(Some(shape_distance), gtfs.shape_length("shape1"));
+
+        gtfs.trips.get_mut("trip1").unwrap().stop_times.clear();
+        gtfs.trips.get_mut("trip1").unwrap().shape_id = None;
+        assert_eq!(None, gtfs.trip_distance("trip1"));
+        assert_eq!(None, gtfs.trip_distance("unknown"));
+    }
+
+    #[test]
+    fn read_without_shapes() {
+        let gtfs = Gtfs::new_without_shapes("fixtures/").unwrap();
+        assert!(gtfs.shapes.is_empty());
+        assert_eq!(5, gtfs.stops.len());
+        assert_eq!(1, gtfs.trips.len());
+
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(1, gtfs.shapes.len());
+    }
 
+    #[test]
+    fn summarize_shapes() {
+        let mut full = Gtfs::default();
+        full.read_shapes(File::open("fixtures/shapes_ordered.txt").unwrap())
+            .unwrap();
         let mut gtfs = Gtfs::default();
+        GtfsReader::default()
+            .summarize_shapes(true)
+            .read_shapes_file(
+                &mut gtfs,
+                File::open("fixtures/shapes_ordered.txt").unwrap(),
+            )
+            .unwrap();
+        assert!(gtfs.shapes.is_empty());
+        assert!(gtfs.warnings.is_empty());
+        for (id, points) in &full.shapes {
+            let summary = gtfs.shape_summaries[id];
+            assert_eq!(points.len(), summary.point_count);
+            assert!((full.shape_length(id).unwrap() - summary.length).abs() < 1e-6);
+            assert_eq!(summary.length, gtfs.shape_length(id).unwrap());
+            let latitudes = points.iter().map(|p| p.latitude);
+            let longitudes = points.iter().map(|p| p.longitude);
+            assert_eq!(
+                (
+                    (
+                        latitudes.clone().fold(f64::INFINITY, f64::min),
+                        longitudes.clone().fold(f64::INFINITY, f64::min)
+                    ),
+                    (
+                        latitudes.fold(f64::NEG_INFINITY, f64::max),
+                        longitudes.fold(f64::NEG_INFINITY, f64::max)
+                    )
+                ),
+                summary.bounding_box()
+            );
+        }
 
-        gtfs.read_trips(File::open(p.join("trips.txt"))?)?;
-        gtfs.read_calendars(calendar_file)?;
-        gtfs.read_calendar_dates(calendar_dates_file)?;
-        gtfs.read_stops(stops_file)?;
-        gtfs.read_routes(routes_file)?;
-        gtfs.read_stop_times(stop_times_file)?;
-        gtfs.read_agencies(agencies_file)?;
+        let gtfs = GtfsReader::default()
+            .summarize_shapes(true)
+            .read("fixtures/")
+            .unwrap();
+        assert_eq!(3, gtfs.shape_summaries["shape1"].point_count);
+        assert_eq!(1, gtfs.warnings.len());
+    }
 
-        gtfs.read_duration = Utc::now().signed_duration_since(now).num_milliseconds();
-        Ok(gtfs)
+    #[test]
+    fn read_shape_lengths_only() {
+        let full = Gtfs::new("fixtures/").unwrap();
+        let gtfs = GtfsReader::default()
+            .shape_lengths_only(true)
+            .read("fixtures/")
+            .unwrap();
+        assert!(gtfs.shapes.is_empty());
+        assert_eq!(1, gtfs.shape_lengths.len());
+        assert_eq!(full.shape_length("shape1"), gtfs.shape_length("shape1"));
+        assert!(gtfs.shape_length("shape1").unwrap() > 0.0);
+        assert_eq!(None, gtfs.shape_length("unknown"));
     }
 
-    pub fn from_zip(file: &str) -> Result<Gtfs, Error> {
-        let reader = File::open(file)?;
-        Gtfs::from_reader(reader)
+    #[test]
+    fn read_fares() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_fare_attributes(File::open("fixtures/fare_attributes.txt").unwrap())
+            .unwrap();
+        gtfs.read_fare_rules(File::open("fixtures/fare_rules.txt").unwrap())
+            .unwrap();
+        let fare = gtfs.get_fare_attribute("50400").unwrap();
+        assert_eq!(1.5, fare.price);
+        assert_eq!("EUR", fare.currency);
+        assert_eq!(PaymentMethod::Aboard, fare.payment_method);
+        assert_eq!(Transfers::Unlimited, fare.transfers);
+        assert_eq!(Some(3600), fare.transfer_duration);
+        assert_eq!(
+            Transfers::NoTransfer,
+            gtfs.get_fare_attribute("50401").unwrap().transfers
+        );
+        assert_eq!(2, gtfs.fare_rules.len());
     }
 
-    #[cfg(feature = "read-url")]
-    pub fn from_url(url: &str) -> Result<Gtfs, Error> {
-        let mut res = reqwest::get(url)?;
-        let mut body = Vec::new();
-        res.read_to_end(&mut body)?;
-        let cursor = std::io::Cursor::new(body);
-        Gtfs::from_reader(cursor)
+    #[test]
+    fn read_fare_products() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(2, gtfs.fare_products.len());
+        let single_ride = gtfs.get_fare_product("single_ride").unwrap();
+        assert_eq!(Some("Single ride".to_owned()), single_ride.name);
+        assert_eq!(2.1, single_ride.amount);
+        assert_eq!("EUR", single_ride.currency);
+        assert!(single_ride.has_valid_currency());
+        assert_eq!(
+            Some("card".to_owned()),
+            gtfs.get_fare_product("day_pass").unwrap().fare_media_id
+        );
+        assert!(gtfs.get_fare_product("unknown").is_err());
+
+        assert_eq!(2, gtfs.fare_leg_rules.len());
+        let rule = &gtfs.fare_leg_rules[1];
+        assert_eq!(Some("core".to_owned()), rule.leg_group_id);
+        assert_eq!(Some("zone1".to_owned()), rule.from_area_id);
+        assert_eq!("day_pass", rule.fare_product_id);
+        assert_eq!(None, gtfs.fare_leg_rules[0].to_area_id);
     }
 
-    pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: T) -> Result<Gtfs, Error> {
-        let now = Utc::now();
-        let mut archive = zip::ZipArchive::new(reader)?;
-        let mut result = Gtfs::default();
-        let mut stop_times_index = None;
-        for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-            if file.name().ends_with("calendar.txt") {
-                result.read_calendars(file)?;
-            } else if file.name().ends_with("stops.txt") {
-                result.read_stops(file)?;
-            } else if file.name().ends_with("calendar_dates.txt") {
-                result.read_calendar_dates(file)?;
-            } else if file.name().ends_with("routes.txt") {
-                result.read_routes(file)?;
-            } else if file.name().ends_with("trips.txt") {
-                result.read_trips(file)?;
-            } else if file.name().ends_with("stop_times.txt") {
-                stop_times_index = Some(i);
-            } else if file.name().ends_with("agency.txt") {
-                result.read_agencies(file)?;
-            }
-        }
-        let index = stop_times_index.ok_or_else(|| format_err!("Missing stop_times.txt"))?;
-        result.read_stop_times(archive.by_index(index)?)?;
+    #[test]
+    fn read_frequencies() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert!(gtfs.is_frequency_based());
+        let frequencies = &gtfs.frequencies["trip1"];
+        assert_eq!(2, frequencies.len());
+        assert_eq!(6 * 3600, frequencies[0].start_time);
+        assert_eq!(9 * 3600, frequencies[0].end_time);
+        assert_eq!(600, frequencies[0].headway_secs);
+        assert_eq!(ExactTimes::ScheduleBased, frequencies[0].exact_times);
+        assert_eq!(ExactTimes::FrequencyBased, frequencies[1].exact_times);
 
-        result.read_duration = Utc::now().signed_duration_since(now).num_milliseconds();
-        Ok(result)
+        let gtfs = Gtfs::from_zip("fixtures/gtfs.zip").unwrap();
+        assert!(!gtfs.is_frequency_based());
     }
 
-    fn read_calendars<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        let mut reader = csv::Reader::from_reader(reader);
-        self.calendar = reader
-            .deserialize()
-            .map(|res| res.map(|e: Calendar| (e.id.to_owned(), e)))
-            .collect::<Result<_, _>>()?;
+    #[test]
+    fn read_location_groups() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(5, gtfs.stops.len());
+        assert_eq!(2, gtfs.location_groups.len());
+        assert_eq!(
+            Some("Downtown on-demand zone".to_owned()),
+            gtfs.get_location_group("downtown").unwrap().name
+        );
+        assert_eq!(None, gtfs.get_location_group("unnamed").unwrap().name);
+        let stops: Vec<&str> = gtfs
+            .stops_in_location_group("downtown")
+            .iter()
+            .map(|stop| stop.id.as_str())
+            .collect();
+        assert_eq!(vec!["stop2", "stop4"], stops);
+        assert!(gtfs.stops_in_location_group("unnamed").is_empty());
+        assert!(gtfs.get_location_group("unknown").is_err());
+    }
 
-        Ok(())
+    #[test]
+    fn read_booking_rules() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(2, gtfs.booking_rules.len());
+        let same_day = gtfs.get_booking_rule("same_day").unwrap();
+        assert_eq!(BookingType::SameDay, same_day.booking_type);
+        assert_eq!(Some(60), same_day.prior_notice_duration_min);
+        assert_eq!(None, same_day.prior_notice_last_time);
+        let prior_day = gtfs.get_booking_rule("prior_day").unwrap();
+        assert_eq!(BookingType::PriorDay, prior_day.booking_type);
+        assert_eq!(Some(1), prior_day.prior_notice_last_day);
+        assert_eq!(Some(17 * 3600), prior_day.prior_notice_last_time);
+        assert_eq!(Some(8 * 3600), prior_day.prior_notice_start_time);
+        assert_eq!(
+            Some("service1".to_owned()),
+            prior_day.prior_notice_service_id
+        );
+        assert!(gtfs.get_booking_rule("unknown").is_err());
     }
 
-    fn read_calendar_dates<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        let mut reader = csv::Reader::from_reader(reader);
-        for result in reader.deserialize() {
-            let record: CalendarDate = result?;
-            let calendar_date = self
-                .calendar_dates
-                .entry(record.service_id.to_owned())
-                .or_insert_with(Vec::new);
-            calendar_date.push(record);
+    #[test]
+    fn transfer_duration() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_fare_attributes(File::open("fixtures/fare_attributes.txt").unwrap())
+            .unwrap();
+        let duration = gtfs
+            .get_fare_attribute("50400")
+            .unwrap()
+            .transfer_duration_as_chrono()
+            .unwrap();
+        assert_eq!(Duration::hours(1), duration);
+        assert_eq!("1h", HumanDuration(duration).to_string());
+        assert_eq!(
+            None,
+            gtfs.get_fare_attribute("50401")
+                .unwrap()
+                .transfer_duration_as_chrono()
+        );
+        assert_eq!(
+            "1h30min5s",
+            HumanDuration(Duration::seconds(5405)).to_string()
+        );
+        assert_eq!("45min", HumanDuration(Duration::minutes(45)).to_string());
+        assert_eq!("0s", HumanDuration(Duration::zero()).to_string());
+    }
+
+    #[test]
+    fn validate_currencies() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert!(gtfs.validate_currencies().is_empty());
+
+        let mut gtfs = Gtfs::default();
+        gtfs.read_fare_attributes(
+            File::open("fixtures/fare_attributes_invalid_currency.txt").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(vec!["lowercase", "typo"], gtfs.validate_currencies());
+    }
+
+    #[test]
+    fn applicable_fares() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let mut fares: Vec<_> = gtfs
+            .applicable_fares("1", Some("zone1"), Some("zone2"))
+            .iter()
+            .map(|f| f.id.as_str())
+            .collect();
+        fares.sort();
+        assert_eq!(vec!["50400", "50401"], fares);
+
+        let fares = gtfs.applicable_fares("1", Some("zone2"), Some("zone1"));
+        assert_eq!(1, fares.len());
+        assert_eq!("50400", fares[0].id);
+
+        let fares = gtfs.applicable_fares("invalid_type", Some("zone1"), Some("zone2"));
+        assert_eq!(1, fares.len());
+    }
+
+    #[test]
+    fn ids() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let mut stop_ids = gtfs.stop_ids();
+        stop_ids.sort();
+        assert_eq!(vec!["stop1", "stop2", "stop3", "stop4", "stop5"], stop_ids);
+        let mut route_ids = gtfs.route_ids();
+        route_ids.sort();
+        assert_eq!(vec!["1", "invalid_type"], route_ids);
+        assert_eq!(vec!["trip1"], gtfs.trip_ids());
+        assert_eq!(vec!["shape1"], gtfs.shape_ids());
+    }
+
+    #[test]
+    fn content_eq() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let mut reordered = Gtfs::new("fixtures/").unwrap();
+        reordered
+            .trips
+            .get_mut("trip1")
+            .unwrap()
+            .stop_times
+            .reverse();
+        for dates in reordered.calendar_dates.values_mut() {
+            dates.reverse();
         }
-        Ok(())
+        reordered.shapes.get_mut("shape1").unwrap().reverse();
+        reordered.warnings.push("ignored".to_owned());
+        assert!(gtfs.content_eq(&reordered));
+        assert!(reordered.content_eq(&gtfs));
+
+        reordered.stops.insert(
+            "stop1".to_owned(),
+            Arc::new(Stop {
+                name: "Renamed".to_owned(),
+                ..gtfs.get_stop("stop1").unwrap().clone()
+            }),
+        );
+        assert!(!gtfs.content_eq(&reordered));
     }
 
-    fn read_stops<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        let mut reader = csv::Reader::from_reader(reader);
-        self.stops = reader
-            .deserialize()
-            .map(|res| res.map(|e: Stop| (e.id.to_owned(), Arc::new(e))))
-            .collect::<Result<_, _>>()?;
+    #[test]
+    fn bounding_box() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            Some((48.796058, 2.449386, 48.796058, 2.449386)),
+            gtfs.bounding_box(false)
+        );
+        assert_eq!(
+            Some((48.795058, 2.448386, 48.797058, 2.450386)),
+            gtfs.bounding_box(true)
+        );
 
-        Ok(())
+        gtfs.stops.clear();
+        gtfs.shapes.clear();
+        assert_eq!(None, gtfs.bounding_box(true));
     }
 
-    fn read_routes<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        let mut reader = csv::Reader::from_reader(reader);
-        self.routes = reader
-            .deserialize()
-            .map(|res| res.map(|e: Route| (e.id.to_owned(), e)))
-            .collect::<Result<_, _>>()?;
-        Ok(())
+    #[test]
+    fn content_hash() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let mut copy = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(gtfs.content_hash(), copy.content_hash());
+
+        copy.trips.get_mut("trip1").unwrap().stop_times.reverse();
+        copy.shapes.get_mut("shape1").unwrap().reverse();
+        assert_eq!(gtfs.content_hash(), copy.content_hash());
+
+        copy.stops.insert(
+            "stop1".to_owned(),
+            Arc::new(Stop {
+                name: "Renamed".to_owned(),
+                ..gtfs.get_stop("stop1").unwrap().clone()
+            }),
+        );
+        assert_ne!(gtfs.content_hash(), copy.content_hash());
     }
 
-    fn read_trips<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        let mut reader = csv::Reader::from_reader(reader);
-        self.trips = reader
-            .deserialize()
-            .map(|res| res.map(|e: Trip| (e.id.to_owned(), e)))
-            .collect::<Result<_, _>>()?;
+    #[test]
+    fn content_hash_ignores_last_shape_point() {
+        let summary = ShapeSummary {
+            point_count: 2,
+            length: 10.0,
+            ..ShapeSummary::default()
+        };
+        let summarizing = ShapeSummary {
+            last_point: Some((2, 48.8, 2.3)),
+            ..summary
+        };
+        assert_eq!(summary, summarizing);
 
-        Ok(())
+        let mut gtfs = Gtfs::default();
+        gtfs.shape_summaries.insert("shape1".to_owned(), summary);
+        let mut other = Gtfs::default();
+        other
+            .shape_summaries
+            .insert("shape1".to_owned(), summarizing);
+        assert_eq!(gtfs.content_hash(), other.content_hash());
     }
 
-    fn read_agencies<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        let mut reader = csv::Reader::from_reader(reader);
-        self.agencies = reader.deserialize().collect::<Result<_, _>>()?;
+    #[test]
+    fn duplicate_stop_names() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops_same_name.txt").unwrap())
+            .unwrap();
+        assert_eq!(
+            vec![vec!["gare_1", "gare_2", "gare_3"]],
+            gtfs.duplicate_stop_names(200.0)
+        );
+        assert!(gtfs.duplicate_stop_names(10_000.0).is_empty());
+        assert_eq!(2, gtfs.duplicate_stop_names(10.0).len());
+    }
 
-        Ok(())
+    #[test]
+    fn root_station() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_stops(File::open("fixtures/stops_hierarchy.txt").unwrap())
+            .unwrap();
+        let root = |id| gtfs.root_station(id).map(|stop| stop.id.as_str());
+        assert_eq!(Some("quay"), root("quay"));
+        assert_eq!(Some("station"), root("platform"));
+        assert_eq!(Some("station"), root("station"));
+        assert_eq!(Some("station"), root("entrance"));
+        assert_eq!(Some("under_stop"), root("under_stop"));
+        assert_eq!(None, root("loop_a"));
+        assert_eq!(None, root("self_loop"));
+        assert_eq!(None, root("unknown"));
+        assert_eq!(Some(2), gtfs.stop_depth("quay"));
+        assert_eq!(Some(0), gtfs.stop_depth("station"));
+        assert_eq!(None, gtfs.stop_depth("loop_b"));
+    }
+
+    #[test]
+    fn unused_stops() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let ids = |gtfs: &Gtfs| {
+            gtfs.unused_stops()
+                .iter()
+                .map(|stop| stop.id.to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["stop1", "stop4", "stop5"], ids(&gtfs));
+
+        let trip = gtfs.trips.get_mut("trip1").unwrap();
+        trip.stop_times[1].stop = Arc::new(Stop {
+            parent_station: Some("stop1".to_owned()),
+            ..trip.stop_times[1].stop.as_ref().clone()
+        });
+        gtfs.stops
+            .insert("stop3".to_owned(), trip.stop_times[1].stop.clone());
+        assert_eq!(vec!["stop4", "stop5"], ids(&gtfs));
     }
 
-    fn read_stop_times<T: std::io::Read>(&mut self, reader: T) -> Result<(), Error> {
-        for stop_time in csv::Reader::from_reader(reader).deserialize() {
-            let s: StopTimeGtfs = stop_time?;
-            let trip = &mut self.trips.get_mut(&s.trip_id).ok_or(ReferenceError {
-                id: s.trip_id.to_string(),
-            })?;
-            let stop = self.stops.get_mut(&s.stop_id).ok_or(ReferenceError {
-                id: s.stop_id.to_string(),
-            })?;
-            trip.stop_times.push(StopTime::from(&s, Arc::clone(&stop)));
+    #[test]
+    fn add_prefix() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        gtfs.trips.get_mut("trip1").unwrap().route_id = "1".to_owned();
+        gtfs.trips.get_mut("trip1").unwrap().shape_id = Some("shape1".to_owned());
+        gtfs.add_prefix("a:");
+
+        assert!(gtfs.get_stop("stop1").is_err());
+        let trip = gtfs.get_trip("a:trip1").unwrap();
+        assert_eq!("a:1", gtfs.get_route(&trip.route_id).unwrap().id);
+        assert_eq!(1, gtfs.routes_in_network("a:urban").len());
+        assert_eq!(Some("a:bus".to_owned()), gtfs.fare_leg_rules[0].network_id);
+        assert_eq!(
+            "a:service1",
+            gtfs.get_calendar(&trip.service_id).unwrap().id
+        );
+        assert!(gtfs.get_shape(trip.shape_id.as_ref().unwrap()).is_ok());
+        for stop_time in &trip.stop_times {
+            assert!(Arc::ptr_eq(
+                &stop_time.stop,
+                &gtfs.stops[stop_time.stop.id.as_str()]
+            ));
         }
+        assert_eq!(
+            Some("a:1".to_owned()),
+            gtfs.get_stop("a:stop3").unwrap().parent_station
+        );
+        assert!(gtfs.get_calendar_date("a:service2").is_ok());
+        assert_eq!("a:50400", gtfs.get_fare_attribute("a:50400").unwrap().id);
+        assert!(gtfs.fare_rules.contains_key("a:50401"));
+        assert_eq!(Some("a:stop3".to_owned()), gtfs.transfers[0].from_stop_id);
+        assert_eq!(Some("a:trip1".to_owned()), gtfs.transfers[3].from_trip_id);
+        assert_eq!(
+            Some("a:service1".to_owned()),
+            gtfs.get_booking_rule("a:prior_day")
+                .unwrap()
+                .prior_notice_service_id
+        );
+    }
 
-        for trip in &mut self.trips.values_mut() {
-            trip.stop_times
-                .sort_by(|a, b| a.stop_sequence.cmp(&b.stop_sequence))
+    #[test]
+    fn ordered() {
+        let ids = |gtfs: &Gtfs| {
+            (
+                gtfs.stops_ordered()
+                    .iter()
+                    .map(|s| s.id.to_owned())
+                    .collect::<Vec<_>>(),
+                gtfs.routes_ordered()
+                    .iter()
+                    .map(|r| r.id.to_owned())
+                    .collect::<Vec<_>>(),
+                gtfs.trips_ordered()
+                    .iter()
+                    .map(|t| t.id.to_owned())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let (stops, routes, trips) = ids(&Gtfs::new("fixtures/").unwrap());
+        assert_eq!(vec!["stop1", "stop2", "stop3", "stop4", "stop5"], stops);
+        assert_eq!(vec!["1", "invalid_type"], routes);
+        assert_eq!(vec!["trip1"], trips);
+        for _ in 0..5 {
+            assert_eq!(
+                (stops.clone(), routes.clone(), trips.clone()),
+                ids(&Gtfs::new("fixtures/").unwrap())
+            );
         }
+    }
 
-        Ok(())
+    #[test]
+    fn routes_with_trips() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        gtfs.trips.get_mut("trip1").unwrap().route_id = "1".to_owned();
+        let routes: Vec<_> = gtfs.routes_with_trips().collect();
+        assert_eq!(gtfs.routes.len(), routes.len());
+        for (route, trips) in routes {
+            assert_eq!(gtfs.trips_for_route(&route.id), trips);
+        }
+        assert_eq!(1, gtfs.trips_for_route("1").len());
+        assert!(gtfs.trips_for_route("invalid_type").is_empty());
     }
 
-    pub fn trip_days(&self, service_id: &str, start_date: NaiveDate) -> Vec<u16> {
-        let mut result = Vec::new();
+    #[test]
+    fn routes_at_stop() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        gtfs.trips.get_mut("trip1").unwrap().route_id = "1".to_owned();
+        let routes = gtfs.routes_at_stop("stop2");
+        assert_eq!(
+            vec!["1"],
+            routes.iter().map(|r| r.id.as_str()).collect::<Vec<_>>()
+        );
+        assert!(gtfs.routes_at_stop("stop1").is_empty());
+    }
 
-        // Handle services given by specific days and exceptions
-        let mut removed_days = HashSet::new();
-        for extra_day in self
-            .calendar_dates
-            .get(service_id)
-            .iter()
-            .flat_map(|e| e.iter())
-        {
-            let offset = extra_day.date.signed_duration_since(start_date).num_days();
-            if offset >= 0 {
-                if extra_day.exception_type == 1 {
-                    result.push(offset as u16);
-                } else if extra_day.exception_type == 2 {
-                    removed_days.insert(offset);
-                }
-            }
-        }
+    #[cfg(feature = "timezone")]
+    #[test]
+    fn departure_datetime() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2017, 1, 15, 13, 0, 0).unwrap()),
+            gtfs.departure_datetime("trip1", 0, NaiveDate::from_ymd_opt(2017, 1, 15).unwrap())
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2017, 7, 1, 12, 0, 0).unwrap()),
+            gtfs.departure_datetime("trip1", 0, NaiveDate::from_ymd_opt(2017, 7, 1).unwrap())
+        );
+        assert_eq!(
+            None,
+            gtfs.departure_datetime("trip1", 42, NaiveDate::from_ymd_opt(2017, 7, 1).unwrap())
+        );
+    }
 
-        if let Some(calendar) = self.calendar.get(service_id) {
-            let total_days = calendar
-                .end_date
-                .signed_duration_since(start_date)
-                .num_days();
-            for days_offset in 0..=total_days {
-                let current_date = start_date + Duration::days(days_offset);
+    #[test]
+    fn validate_stop_time_ordering() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert!(gtfs.validate_stop_time_ordering().is_empty());
 
-                if calendar.start_date <= current_date
-                    && calendar.end_date >= current_date
-                    && calendar.valid_weekday(current_date)
-                    && !removed_days.contains(&days_offset)
-                {
-                    result.push(days_offset as u16);
-                }
-            }
+        let mut gtfs = Gtfs::default();
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
+            .unwrap();
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_unordered.txt").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![("trip1".to_owned(), 3)],
+            gtfs.validate_stop_time_ordering()
+        );
+    }
+
+    #[test]
+    fn allows_pickup_and_drop_off() {
+        let stop_time = |value| StopTime {
+            pickup_type: value,
+            drop_off_type: value,
+            ..StopTime::default()
+        };
+        for (value, allowed, arrangement) in &[
+            (None, true, false),
+            (Some(PickupDropOffType::Regular), true, false),
+            (Some(PickupDropOffType::NotAvailable), false, false),
+            (Some(PickupDropOffType::ArrangeByPhone), true, true),
+            (Some(PickupDropOffType::CoordinateWithDriver), true, true),
+        ] {
+            let st = stop_time(*value);
+            assert_eq!(*allowed, st.allows_pickup());
+            assert_eq!(*allowed, st.allows_drop_off());
+            assert_eq!(
+                *arrangement,
+                value.map_or(false, |value| value.requires_arrangement())
+            );
         }
+    }
 
-        result
+    #[test]
+    fn naive_times() {
+        let stop_time = StopTime {
+            arrival_time: Some(parse_time("23:59:30").unwrap()),
+            departure_time: Some(parse_time("25:00:00").unwrap()),
+            ..StopTime::default()
+        };
+        assert_eq!(
+            NaiveTime::from_hms_opt(23, 59, 30),
+            stop_time.arrival_naive_time()
+        );
+        assert_eq!(None, stop_time.departure_naive_time());
+        assert_eq!(None, StopTime::default().arrival_naive_time());
     }
 
-    pub fn get_stop<'a>(&'a self, id: &str) -> Result<&'a Stop, ReferenceError> {
-        match self.stops.get(id) {
-            Some(stop) => Ok(stop),
-            None => Err(ReferenceError { id: id.to_owned() }),
-        }
+    #[test]
+    fn trip_terminals() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let trip = gtfs.get_trip("trip1").unwrap();
+        assert_eq!("stop2", trip.first_stop().unwrap().id);
+        assert_eq!("stop3", trip.last_stop().unwrap().id);
+        assert_eq!(Some(15 * 3600), trip.end_time());
+
+        let empty = Trip::default();
+        assert_eq!(None, empty.first_stop());
+        assert_eq!(None, empty.last_stop());
+        assert_eq!(None, empty.end_time());
     }
 
-    pub fn get_trip<'a>(&'a self, id: &str) -> Result<&'a Trip, ReferenceError> {
-        match self.trips.get(id) {
-            Some(trip) => Ok(trip),
-            None => Err(ReferenceError { id: id.to_owned() }),
+    #[test]
+    fn trips_for_route_sorted() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let trip = gtfs.get_trip("trip1").unwrap().clone();
+        assert_eq!(Some(14 * 3600), trip.start_time());
+
+        let mut early = trip.clone();
+        early.id = "early".to_owned();
+        early.stop_times[0].departure_time = Some(8 * 3600);
+        let mut empty = trip.clone();
+        empty.id = "empty".to_owned();
+        empty.stop_times.clear();
+        for t in [early, empty] {
+            gtfs.trips.insert(t.id.to_owned(), t);
         }
+
+        let ids: Vec<_> = gtfs
+            .trips_for_route_sorted("route1")
+            .iter()
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(vec!["early", "trip1", "empty"], ids);
     }
 
-    pub fn get_route<'a>(&'a self, id: &str) -> Result<&'a Route, ReferenceError> {
-        match self.routes.get(id) {
-            Some(route) => Ok(route),
-            None => Err(ReferenceError { id: id.to_owned() }),
-        }
+    #[test]
+    fn stop_counts() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_count());
+        assert_eq!(2, gtfs.route_stop_count("route1"));
+        assert_eq!(0, gtfs.route_stop_count("1"));
+
+        let mut other_trip = gtfs.get_trip("trip1").unwrap().clone();
+        other_trip.id = "trip2".to_owned();
+        gtfs.trips.insert(other_trip.id.to_owned(), other_trip);
+        assert_eq!(2, gtfs.route_stop_count("route1"));
+    }
+
+    #[test]
+    fn read_agency_extra_columns() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/agency_extra.txt").unwrap())
+            .unwrap();
+        let agency = &gtfs.agencies[0];
+        assert_eq!(Some("848".to_owned()), agency.id);
+        assert_eq!(Some("0298".to_owned()), agency.phone);
+        assert_eq!(4, agency.extra.len());
+        assert_eq!("bibus-app", agency.extra["ticketing_deep_link_id"]);
+        assert_eq!("1", agency.extra["cemv_support"]);
+        assert_eq!("00123", agency.extra["ticket_code"]);
+        assert_eq!("1.50", agency.extra["ratio"]);
+        assert_eq!(None, gtfs.agencies[1].lang);
+        assert!(gtfs.agencies[1].extra.values().all(|v| v.is_empty()));
+        assert!(Gtfs::new("fixtures/").unwrap().agencies[0].extra.is_empty());
+    }
+
+    #[test]
+    fn read_agencies() {
+        let mut gtfs = Gtfs::default();
+        gtfs.read_agencies(File::open("fixtures/agency.txt").unwrap())
+            .unwrap();
+        let agencies = &gtfs.agencies;
+        assert_eq!("BIBUS", agencies[0].name);
+        assert_eq!("http://www.bibus.fr", agencies[0].url);
+        assert_eq!("Europe/Paris", agencies[0].timezone);
     }
 
-    pub fn get_calendar<'a>(&'a self, id: &str) -> Result<&'a Calendar, ReferenceError> {
-        match self.calendar.get(id) {
-            Some(calendar) => Ok(calendar),
-            None => Err(ReferenceError { id: id.to_owned() }),
-        }
-    }
+    #[test]
+    fn stop_timezone() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(Some("Europe/Paris"), gtfs.stop_timezone("stop2"));
+        assert_eq!(None, gtfs.stop_timezone("unknown"));
 
-    pub fn get_calendar_date<'a>(
-        &'a self,
-        id: &str,
-    ) -> Result<&'a Vec<CalendarDate>, ReferenceError> {
-        match self.calendar_dates.get(id) {
-            Some(calendar_dates) => Ok(calendar_dates),
-            None => Err(ReferenceError { id: id.to_owned() }),
-        }
+        gtfs.agencies.push(Agency {
+            id: Some("848".to_owned()),
+            timezone: "America/Montreal".to_owned(),
+            ..Agency::default()
+        });
+        gtfs.trips.get_mut("trip1").unwrap().route_id = "1".to_owned();
+        assert_eq!(Some("America/Montreal"), gtfs.stop_timezone("stop2"));
+        assert_eq!(Some("Europe/Paris"), gtfs.stop_timezone("stop4"));
+
+        let mut stop = gtfs.get_stop("stop4").unwrap().clone();
+        stop.timezone = Some("Asia/Tokyo".to_owned());
+        gtfs.stops.insert(stop.id.to_owned(), Arc::new(stop));
+        assert_eq!(Some("Asia/Tokyo"), gtfs.stop_timezone("stop4"));
     }
-}
 
-fn deserialize_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s = String::deserialize(deserializer)?;
-    Ok(s == "1")
-}
+    #[test]
+    fn read_transfers() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(4, gtfs.transfers.len());
+        let transfer = &gtfs.transfers[0];
+        assert_eq!(Some("stop3".to_owned()), transfer.from_stop_id);
+        assert_eq!(TransferType::MinTime, transfer.transfer_type);
+        assert_eq!(Some(120), transfer.min_transfer_time);
+        assert_eq!(None, gtfs.transfers[1].min_transfer_time);
+        let in_seat = &gtfs.transfers[3];
+        assert_eq!(TransferType::InSeat, in_seat.transfer_type);
+        assert_eq!(None, in_seat.from_stop_id);
+        assert_eq!(Some("trip1".to_owned()), in_seat.to_trip_id);
+        assert_eq!(Some("1".to_owned()), in_seat.from_route_id);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[cfg(feature = "graph")]
+    #[test]
+    fn to_stop_graph() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let graph = gtfs.to_stop_graph();
+        assert_eq!(5, graph.node_count());
+        // one connection for trip1 and two possible transfers
+        assert_eq!(3, graph.edge_count());
+        let stop = |id| graph.node_indices().find(|i| graph[*i].id == id).unwrap();
+        let edge = graph.find_edge(stop("stop2"), stop("stop3")).unwrap();
+        assert_eq!(
+            EdgeWeight::Connection {
+                travel_time: Some(3600)
+            },
+            graph[edge]
+        );
+        let edge = graph.find_edge(stop("stop3"), stop("stop4")).unwrap();
+        assert_eq!(
+            EdgeWeight::Transfer {
+                min_transfer_time: Some(120)
+            },
+            graph[edge]
+        );
+        assert!(graph.find_edge(stop("stop2"), stop("stop5")).is_none());
+    }
 
+    #[cfg(feature = "rt")]
     #[test]
-    fn read_calendar() {
+    fn apply_trip_update() {
+        use gtfs_realtime::trip_update::{StopTimeEvent, StopTimeUpdate};
+        use gtfs_realtime::{TripDescriptor, TripUpdate};
+
         let mut gtfs = Gtfs::default();
-        gtfs.read_calendars(File::open("fixtures/calendar.txt").unwrap())
+        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
             .unwrap();
-        assert_eq!(1, gtfs.calendar.len());
-        assert!(!gtfs.calendar["service1"].monday);
-        assert!(gtfs.calendar["service1"].saturday);
+        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+            .unwrap();
+        gtfs.read_agencies(File::open("fixtures/agency.txt").unwrap())
+            .unwrap();
+        GtfsReader::default()
+            .read_stop_times_file(
+                &mut gtfs,
+                File::open("fixtures/stop_times_unordered.txt").unwrap(),
+            )
+            .unwrap();
+        let update = TripUpdate {
+            trip: TripDescriptor {
+                trip_id: Some("trip1".to_owned()),
+                start_date: Some("20170115".to_owned()),
+                ..TripDescriptor::default()
+            },
+            stop_time_update: vec![
+                StopTimeUpdate {
+                    stop_sequence: Some(2),
+                    departure: Some(StopTimeEvent {
+                        delay: Some(120),
+                        ..StopTimeEvent::default()
+                    }),
+                    ..StopTimeUpdate::default()
+                },
+                StopTimeUpdate {
+                    stop_id: Some("stop4".to_owned()),
+                    arrival: Some(StopTimeEvent {
+                        // 10:25 in Paris
+                        time: Some(
+                            Utc.with_ymd_and_hms(2017, 1, 15, 9, 25, 0)
+                                .unwrap()
+                                .timestamp(),
+                        ),
+                        ..StopTimeEvent::default()
+                    }),
+                    ..StopTimeUpdate::default()
+                },
+            ],
+            ..TripUpdate::default()
+        };
+        let adjusted = gtfs.apply_trip_update(&update);
+        assert_eq!(4, adjusted.len());
+        assert_eq!(0, adjusted[0].departure_delay);
+        assert_eq!(0, adjusted[1].arrival_delay);
+        assert_eq!(Some(10 * 3600 + 14 * 60), adjusted[1].departure_time);
+        assert_eq!(120, adjusted[2].arrival_delay);
+        assert_eq!(Some(10 * 3600 + 7 * 60), adjusted[2].arrival_time);
+        assert_eq!(300, adjusted[3].arrival_delay);
+        assert_eq!(Some(10 * 3600 + 25 * 60), adjusted[3].departure_time);
+        assert_eq!(
+            Some(10 * 3600 + 20 * 60),
+            gtfs.trips["trip1"].stop_times[3].arrival_time
+        );
+
+        let unknown = TripUpdate {
+            trip: TripDescriptor {
+                trip_id: Some("unknown".to_owned()),
+                ..TripDescriptor::default()
+            },
+            ..TripUpdate::default()
+        };
+        assert!(gtfs.apply_trip_update(&unknown).is_empty());
     }
 
     #[test]
-    fn read_calendar_dates() {
+    fn agencies_sorted() {
         let mut gtfs = Gtfs::default();
-        gtfs.read_calendar_dates(File::open("fixtures/calendar_dates.txt").unwrap())
+        gtfs.read_agencies(File::open("fixtures/agency.txt").unwrap())
             .unwrap();
+        gtfs.agencies.reverse();
+        let names: Vec<_> = gtfs
+            .agencies_sorted()
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(vec!["BIBUS", "Ter"], names);
+    }
+
+    #[test]
+    fn filter_to_date_range() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        gtfs.trips.get_mut("trip1").unwrap().route_id = "1".to_owned();
+        let mut trip2 = gtfs.trips["trip1"].clone();
+        trip2.id = "trip2".to_owned();
+        trip2.service_id = "service2".to_owned();
+        gtfs.trips.insert(trip2.id.to_owned(), trip2);
+        let day = |d| NaiveDate::from_ymd_opt(2017, 1, d).unwrap();
+
+        gtfs.filter_to_date_range(day(2), day(7));
+        assert!(gtfs.get_trip("trip1").is_ok());
+        assert!(gtfs.get_trip("trip2").is_err());
         assert_eq!(2, gtfs.calendar_dates.len());
-        assert_eq!(2, gtfs.calendar_dates["service1"].len());
-        assert_eq!(2, gtfs.calendar_dates["service1"][0].exception_type);
-        assert_eq!(1, gtfs.calendar_dates["service2"][0].exception_type);
+        assert_eq!(5, gtfs.stops.len());
+
+        gtfs.remove_unreferenced();
+        assert!(gtfs.get_calendar_date("service2").is_err());
+        assert!(gtfs.get_calendar("service1").is_ok());
+        assert!(gtfs.shapes.is_empty());
+        let mut stops: Vec<&String> = gtfs.stops.keys().collect();
+        stops.sort();
+        assert_eq!(vec!["stop2", "stop3"], stops);
+        assert_eq!(vec!["1"], gtfs.routes.keys().collect::<Vec<_>>());
+        assert_eq!(2, gtfs.agencies.len());
+
+        gtfs.filter_to_date_range(day(13), day(9));
+        assert!(gtfs.get_trip("trip1").is_ok());
+        gtfs.filter_to_date_range(day(9), day(13));
+        assert!(gtfs.trips.is_empty());
     }
 
     #[test]
-    fn read_stop() {
-        let mut gtfs = Gtfs::default();
-        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
+    fn services_on_weekday() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(vec!["service1"], gtfs.services_on_weekday(Weekday::Sat));
+        assert_eq!(vec!["service1"], gtfs.services_on_weekday(Weekday::Sun));
+        assert!(gtfs.services_on_weekday(Weekday::Mon).is_empty());
+    }
+
+    #[test]
+    fn accessible_trips_on_date() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            Availability::InformationNotAvailable,
+            gtfs.get_trip("trip1").unwrap().wheelchair_accessible
+        );
+        gtfs.read_trips(File::open("fixtures/trips_accessible.txt").unwrap())
             .unwrap();
-        assert_eq!(5, gtfs.stops.len());
+        let trip = gtfs.get_trip("not_accessible").unwrap();
+        assert_eq!(Availability::NotAvailable, trip.wheelchair_accessible);
+        assert_eq!(Availability::InformationNotAvailable, trip.bikes_allowed);
         assert_eq!(
-            LocationType::StopArea,
-            gtfs.get_stop("stop1").unwrap().location_type
+            Availability::NotAvailable,
+            gtfs.get_trip("unknown").unwrap().bikes_allowed
         );
+
+        let ids = |date| -> Vec<String> {
+            gtfs.accessible_trips_on_date(date)
+                .iter()
+                .map(|trip| trip.id.to_owned())
+                .collect()
+        };
         assert_eq!(
-            LocationType::StopPoint,
-            gtfs.get_stop("stop2").unwrap().location_type
+            vec!["accessible"],
+            ids(NaiveDate::from_ymd_opt(2017, 1, 7).unwrap())
         );
         assert_eq!(
-            Some("1".to_owned()),
-            gtfs.get_stop("stop3").unwrap().parent_station
+            vec!["other_day"],
+            ids(NaiveDate::from_ymd_opt(2017, 1, 1).unwrap())
         );
     }
 
     #[test]
-    fn read_routes() {
-        let mut gtfs = Gtfs::default();
-        gtfs.read_routes(File::open("fixtures/routes.txt").unwrap())
-            .unwrap();
-        assert_eq!(2, gtfs.routes.len());
-        assert_eq!(RouteType::Bus, gtfs.get_route("1").unwrap().route_type);
-        assert_eq!(RouteType::Other(42), gtfs.get_route("invalid_type").unwrap().route_type);
+    fn departures_from() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2017, 1, d).unwrap();
+        let departures = gtfs.departures_from("stop2", day(7));
+        assert_eq!(1, departures.len());
+        assert_eq!("trip1", departures[0].0.id);
+        assert_eq!(Some(14 * 3600), departures[0].1.departure_time);
+        assert!(gtfs.departures_from("stop2", day(1)).is_empty());
+        assert!(gtfs.departures_from("stop1", day(7)).is_empty());
+
+        let mut early = gtfs.trips["trip1"].clone();
+        early.id = "early".to_owned();
+        early.stop_times[0].departure_time = Some(9 * 3600);
+        gtfs.trips.insert(early.id.to_owned(), early);
+        let ids: Vec<_> = gtfs
+            .departures_from("stop2", day(7))
+            .iter()
+            .map(|(trip, _)| trip.id.as_str())
+            .collect();
+        assert_eq!(vec!["early", "trip1"], ids);
     }
 
     #[test]
-    fn read_trips() {
-        let mut gtfs = Gtfs::default();
-        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
-            .unwrap();
-        assert_eq!(1, gtfs.trips.len());
+    fn headways_at_stop() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let day = NaiveDate::from_ymd_opt(2017, 1, 7).unwrap();
+        assert!(gtfs.headways_at_stop("stop2", "route1", day).is_empty());
+
+        for (id, hour) in &[("early", 9), ("noon", 12)] {
+            let mut trip = gtfs.trips["trip1"].clone();
+            trip.id = id.to_string();
+            trip.stop_times[0].departure_time = Some(hour * 3600);
+            gtfs.trips.insert(trip.id.to_owned(), trip);
+        }
+        let mut other_route = gtfs.trips["trip1"].clone();
+        other_route.id = "other_route".to_owned();
+        other_route.route_id = "route2".to_owned();
+        other_route.stop_times[0].departure_time = Some(13 * 3600);
+        gtfs.trips.insert(other_route.id.to_owned(), other_route);
+
+        assert_eq!(
+            vec![3 * 3600, 2 * 3600],
+            gtfs.headways_at_stop("stop2", "route1", day)
+        );
+        assert!(gtfs.headways_at_stop("stop3", "route2", day).is_empty());
     }
 
     #[test]
-    fn read_stop_times() {
-        let mut gtfs = Gtfs::default();
-        gtfs.read_trips(File::open("fixtures/trips.txt").unwrap())
-            .unwrap();
-        gtfs.read_stops(File::open("fixtures/stops.txt").unwrap())
-            .unwrap();
-        gtfs.read_stop_times(File::open("fixtures/stop_times.txt").unwrap())
-            .unwrap();
-        let stop_times = &gtfs.trips.get("trip1").unwrap().stop_times;
-        assert_eq!(2, stop_times.len());
+    fn next_departures() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2017, 1, d).unwrap();
+        let ids = |departures: Vec<(&Trip, &StopTime)>| -> Vec<String> {
+            departures
+                .iter()
+                .map(|(trip, _)| trip.id.to_owned())
+                .collect()
+        };
         assert_eq!(
-            PickupDropOffType::Regular,
-            stop_times[0].pickup_type.unwrap()
+            vec!["trip1"],
+            ids(gtfs.next_departures("stop2", day(7), 13 * 3600, 5))
         );
         assert_eq!(
-            PickupDropOffType::NotAvailable,
-            stop_times[0].drop_off_type.unwrap()
+            vec!["trip1"],
+            ids(gtfs.next_departures("stop2", day(7), 14 * 3600, 5))
         );
+        assert!(gtfs
+            .next_departures("stop2", day(7), 15 * 3600, 5)
+            .is_empty());
+
+        let mut late = gtfs.trips["trip1"].clone();
+        late.id = "late".to_owned();
+        late.stop_times[0].departure_time = Some(25 * 3600);
+        gtfs.trips.insert(late.id.to_owned(), late);
         assert_eq!(
-            PickupDropOffType::ArrangeByPhone,
-            stop_times[1].pickup_type.unwrap()
+            vec!["late", "trip1", "late"],
+            ids(gtfs.next_departures("stop2", day(8), 0, 5))
         );
-        assert_eq!(None, stop_times[1].drop_off_type);
+        assert_eq!(
+            vec!["late"],
+            ids(gtfs.next_departures("stop2", day(8), 0, 1))
+        );
+        assert_eq!(
+            vec!["late"],
+            ids(gtfs.next_departures("stop2", day(9), 0, 5))
+        );
+        assert!(gtfs.next_departures("stop2", day(10), 0, 5).is_empty());
     }
 
     #[test]
-    fn read_agencies() {
-        let mut gtfs = Gtfs::default();
-        gtfs.read_agencies(File::open("fixtures/agency.txt").unwrap())
-            .unwrap();
-        let agencies = &gtfs.agencies;
-        assert_eq!("BIBUS", agencies[0].name);
-        assert_eq!("http://www.bibus.fr", agencies[0].url);
-        assert_eq!("Europe/Paris", agencies[0].timezone);
+    fn service_type() {
+        let mut gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            ServiceKind::RegularWithExceptions,
+            gtfs.service_type("service1")
+        );
+        assert_eq!(ServiceKind::ExceptionsOnly, gtfs.service_type("service2"));
+        assert_eq!(ServiceKind::Unknown, gtfs.service_type("unknown"));
+        gtfs.calendar_dates.remove("service1");
+        assert_eq!(
+            ServiceKind::RegularNoExceptions,
+            gtfs.service_type("service1")
+        );
+    }
+
+    #[test]
+    fn route_hourly_frequency() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2017, 1, d).unwrap();
+        assert!(gtfs.is_service_active("service2", day(1)));
+        assert!(!gtfs.is_service_active("service1", day(1)));
+        assert!(gtfs.is_service_active("service1", day(7)));
+        assert!(!gtfs.is_service_active("service1", day(9)));
+
+        let mut expected = [0; 24];
+        expected[14] = 1;
+        assert_eq!(expected, gtfs.route_hourly_frequency("route1", day(7)));
+        assert_eq!([0; 24], gtfs.route_hourly_frequency("route1", day(1)));
+        assert_eq!([0; 24], gtfs.route_hourly_frequency("1", day(7)));
+
+        let mut gtfs = gtfs;
+        let trip = gtfs.trips.get_mut("trip1").unwrap();
+        trip.stop_times[0].departure_time = Some(25 * 3600 + 30 * 60);
+        let mut expected = [0; 24];
+        expected[1] = 1;
+        assert_eq!(expected, gtfs.route_hourly_frequency("route1", day(7)));
     }
 
     #[test]
@@ -745,6 +6784,88 @@ mod tests {
         assert_eq!(vec![0], days2);
     }
 
+    #[test]
+    fn service_date_range() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            Some((
+                NaiveDate::from_ymd(2017, 1, 1),
+                NaiveDate::from_ymd(2017, 1, 15)
+            )),
+            gtfs.service_date_range()
+        );
+        assert_eq!(None, Gtfs::default().service_date_range());
+    }
+
+    #[test]
+    fn calendar_exceptions_in_range() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2017, 1, d).unwrap();
+        let exceptions = gtfs.calendar_exceptions_in_range(day(1), day(1));
+        assert_eq!(
+            vec![("service1", 2), ("service2", 1)],
+            exceptions
+                .iter()
+                .map(|d| (d.service_id.as_str(), d.exception_type))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, gtfs.calendar_exceptions_in_range(day(1), day(2)).len());
+        assert_eq!(1, gtfs.calendar_exceptions_in_range(day(2), day(31)).len());
+        assert!(gtfs
+            .calendar_exceptions_in_range(day(3), day(31))
+            .is_empty());
+    }
+
+    #[test]
+    fn read_preserving_unknown_columns() {
+        let (gtfs, raw) = Gtfs::from_path_preserving("fixtures/").unwrap();
+        assert_eq!(5, gtfs.stops.len());
+        let stops = raw.get_file("stops.txt").unwrap();
+        assert_eq!(5, stops.records.len());
+        let zone_index = stops.headers.iter().position(|h| h == "zone_id");
+        assert!(zone_index.is_some());
+        assert_eq!(Some("stop1"), stops.records[0].get(0));
+        assert!(raw.get_file("trips.txt").is_some());
+    }
+
+    #[test]
+    fn stats() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        assert_eq!(
+            GtfsStats {
+                read_duration: gtfs.read_duration,
+                stops: 5,
+                routes: 2,
+                trips: 1,
+                agencies: 2,
+                shapes: 1,
+                fares: 2,
+                service_date_range: Some((
+                    NaiveDate::from_ymd_opt(2017, 1, 1).unwrap(),
+                    NaiveDate::from_ymd_opt(2017, 1, 15).unwrap()
+                )),
+            },
+            gtfs.stats()
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn to_json() {
+        let gtfs = Gtfs::new("fixtures/").unwrap();
+        let json: serde_json::Value = serde_json::from_str(&gtfs.to_json().unwrap()).unwrap();
+        assert_eq!(5, json["stops"].as_object().unwrap().len());
+        assert_eq!(2, json["routes"].as_object().unwrap().len());
+        assert_eq!(1, json["trips"].as_object().unwrap().len());
+        assert_eq!(2, json["agencies"].as_array().unwrap().len());
+        assert_eq!(1, json["calendar"].as_object().unwrap().len());
+        assert_eq!("20170101", json["calendar"]["service1"]["start_date"]);
+        assert_eq!(42, json["routes"]["invalid_type"]["route_type"]);
+        let stop_times = &json["trips"]["trip1"]["stop_times"];
+        assert_eq!(2, stop_times.as_array().unwrap().len());
+        assert_eq!("stop2", stop_times[0]["stop"]);
+    }
+
     #[test]
     fn read_from_gtfs() {
         let gtfs = Gtfs::from_zip("fixtures/gtfs.zip").unwrap();
@@ -764,6 +6885,298 @@ mod tests {
         assert_eq!("Utopia", gtfs.get_stop("Utopia").unwrap_err().id);
     }
 
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn read_gzipped_members() {
+        let zip = Gtfs::from_zip("fixtures/gtfs_gzipped_members.zip").unwrap();
+        let dir = Gtfs::new("fixtures/gzipped/").unwrap();
+        for gtfs in &[zip, dir] {
+            assert_eq!(5, gtfs.stops.len());
+            assert_eq!("Stop Area", gtfs.get_stop("stop1").unwrap().name);
+            assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_times.len());
+        }
+    }
+
+    #[test]
+    fn reader_without_stop_times() {
+        let reader = GtfsReader::default().read_stop_times(false);
+        let dir = reader.read("fixtures/").unwrap();
+        let zip = reader
+            .read_from_zip("fixtures/gtfs_without_stop_times.zip")
+            .unwrap();
+        for gtfs in &[dir, zip] {
+            assert!(gtfs.get_trip("trip1").unwrap().stop_times.is_empty());
+            assert_eq!(5, gtfs.stops.len());
+        }
+    }
+
+    #[test]
+    fn reader_delimiter() {
+        let mut gtfs = Gtfs::default();
+        GtfsReader::default()
+            .delimiter(b';')
+            .read_file(
+                &mut gtfs,
+                "stops.txt",
+                File::open("fixtures/semicolon/stops.txt").unwrap(),
+            )
+            .unwrap();
+        let stop = gtfs.get_stop("stop1").unwrap();
+        assert_eq!("Gare de l'Est, quai 1", stop.name);
+        assert_eq!(2.359, stop.longitude);
+        assert_eq!("Nation", gtfs.get_stop("stop2").unwrap().name);
+    }
+
+    #[test]
+    fn reader_lenient() {
+        let gtfs = GtfsReader::default()
+            .lenient(true)
+            .read("fixtures/short_rows/")
+            .unwrap();
+        assert_eq!(5, gtfs.stops.len());
+        assert!(GtfsReader::default().read("fixtures/short_rows/").is_err());
+        let reader = GtfsReader::default()
+            .on_missing_stop(OnMissing::Placeholder)
+            .lenient(false);
+        assert_eq!(OnMissing::Placeholder, reader.missing_stop());
+        assert!(!reader.input(&b""[..]).flexible);
+        let reader = GtfsReader::default().lenient(true).lenient(false);
+        assert_eq!(OnMissing::Error, reader.missing_stop());
+        assert!(!reader.input(&b""[..]).flexible);
+        assert!(GtfsReader::default()
+            .lenient(true)
+            .lenient(false)
+            .read("fixtures/short_rows/")
+            .is_err());
+        let reader = GtfsReader::default()
+            .on_missing_stop(OnMissing::Placeholder)
+            .lenient(true);
+        assert_eq!(OnMissing::Placeholder, reader.missing_stop());
+    }
+
+    #[test]
+    fn reader_trim() {
+        let read = |reader: GtfsReader| {
+            let mut gtfs = Gtfs::default();
+            reader
+                .read_file(
+                    &mut gtfs,
+                    "agency.txt",
+                    File::open("fixtures/padded_agency_ids/agency.txt").unwrap(),
+                )
+                .unwrap();
+            gtfs.agencies[1].id.clone()
+        };
+        assert_eq!(Some("2".to_owned()), read(GtfsReader::default()));
+        assert_eq!(
+            Some(" 2".to_owned()),
+            read(GtfsReader::default().trim(false))
+        );
+    }
+
+    #[test]
+    fn read_flexible() {
+        assert!(Gtfs::new("fixtures/short_rows/").is_err());
+        let gtfs = GtfsReader::default()
+            .flexible(true)
+            .read("fixtures/short_rows/")
+            .unwrap();
+        assert_eq!(5, gtfs.stops.len());
+        let stop2 = gtfs.get_stop("stop2").unwrap();
+        assert_eq!("StopPoint", stop2.name);
+        assert_eq!(2.449386, stop2.longitude);
+        assert_eq!(None, stop2.parent_station);
+        assert_eq!(
+            Some("1".to_owned()),
+            gtfs.get_stop("stop5").unwrap().parent_station
+        );
+        assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_times.len());
+    }
+
+    #[cfg(feature = "targz")]
+    #[test]
+    fn read_from_targz() {
+        let gtfs = Gtfs::from_targz("fixtures/gtfs.tar.gz").unwrap();
+        let zip = Gtfs::from_zip("fixtures/gtfs.zip").unwrap();
+        assert_eq!(zip.calendar.len(), gtfs.calendar.len());
+        assert_eq!(zip.calendar_dates.len(), gtfs.calendar_dates.len());
+        assert_eq!(zip.stops.len(), gtfs.stops.len());
+        assert_eq!(zip.routes.len(), gtfs.routes.len());
+        assert_eq!(zip.trips.len(), gtfs.trips.len());
+        assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_times.len());
+    }
+
+    #[test]
+    fn read_from_bytes() {
+        let gtfs = Gtfs::from_bytes(include_bytes!("../fixtures/gtfs.zip")).unwrap();
+        assert_eq!(5, gtfs.stops.len());
+        assert_eq!(2, gtfs.get_trip("trip1").unwrap().stop_times.len());
+    }
+
+    #[test]
+    fn parse_dashed_date() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        assert_eq!(date, parse_date("20240315").unwrap());
+        assert_eq!(date, parse_date("2024-03-15").unwrap());
+        assert!(parse_date("2024-3-15x").is_err());
+        assert!(parse_date("20241315").is_err());
+    }
+
+    #[test]
+    fn from_parts() {
+        let stop = |id: &str| Stop {
+            id: id.to_owned(),
+            ..Stop::default()
+        };
+        let stop_time = |stop_id: &str, stop_sequence, time| StopTimeRow {
+            trip_id: "trip".to_owned(),
+            stop_id: stop_id.to_owned(),
+            stop_sequence,
+            arrival_time: Some(time),
+            departure_time: Some(time),
+            ..StopTimeRow::default()
+        };
+        let gtfs = Gtfs::from_parts(
+            vec![stop("a"), stop("b")],
+            vec![Route {
+                id: "route".to_owned(),
+                ..Route::default()
+            }],
+            vec![Trip {
+                id: "trip".to_owned(),
+                route_id: "route".to_owned(),
+                service_id: "service".to_owned(),
+                ..Trip::default()
+            }],
+            vec![],
+            vec![CalendarDate {
+                service_id: "service".to_owned(),
+                date: NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+                exception_type: 1,
+            }],
+            vec![],
+            vec![stop_time("b", 2, 3600), stop_time("a", 1, 0)],
+        )
+        .unwrap();
+        let trip = gtfs.get_trip("trip").unwrap();
+        assert_eq!("a", trip.first_stop().unwrap().id);
+        assert_eq!(Some(3600), trip.end_time());
+        assert!(Arc::ptr_eq(&trip.stop_times[0].stop, &gtfs.stops["a"]));
+        assert_eq!(
+            1,
+            gtfs.departures_from("a", NaiveDate::from_ymd_opt(2024, 3, 15).unwrap())
+                .len()
+        );
+
+        let err = Gtfs::from_parts(
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![stop_time("a", 1, 0)],
+        )
+        .err()
+        .unwrap();
+        match err {
+            GtfsError::Reference(e) => assert_eq!("trip", e.id),
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        match GtfsReader::default()
+            .read("fixtures/does_not_exist")
+            .err()
+            .unwrap()
+        {
+            GtfsError::Io(_) => (),
+            err => panic!("unexpected error {}", err),
+        }
+        match Gtfs::from_bytes(b"not a zip").err().unwrap() {
+            GtfsError::Zip(_) => (),
+            err => panic!("unexpected error {}", err),
+        }
+        match parse_time("12:30").unwrap_err() {
+            GtfsError::InvalidTime { time, source } => {
+                assert_eq!("12:30", time);
+                assert!(source.is_none());
+            }
+            err => panic!("unexpected error {}", err),
+        }
+        let err = parse_time("12:3O:00").unwrap_err();
+        assert!(matches!(err, GtfsError::InvalidTime { .. }));
+        assert!(std::error::Error::source(&err)
+            .unwrap()
+            .is::<std::num::ParseIntError>());
+        let err = parse_date("01/01/2017").unwrap_err();
+        match &err {
+            GtfsError::InvalidDate { date, .. } => assert_eq!("01/01/2017", date),
+            err => panic!("unexpected error {}", err),
+        }
+        assert!(std::error::Error::source(&err)
+            .unwrap()
+            .is::<chrono::ParseError>());
+    }
+
+    #[test]
+    fn check_required_files() {
+        assert!(Gtfs::check_required_files("fixtures/").is_ok());
+        let err = Gtfs::new("fixtures/incomplete").err().unwrap();
+        assert_eq!(
+            "Missing files routes.txt, trips.txt, stop_times.txt",
+            err.to_string()
+        );
+        match Gtfs::check_required_files("fixtures/does_not_exist").unwrap_err() {
+            GtfsError::MissingFiles(files) => {
+                assert_eq!(6, files.len());
+                assert_eq!("calendar.txt or calendar_dates.txt", files[5]);
+            }
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn read_without_stop_times() {
+        for result in &[
+            Gtfs::from_zip("fixtures/gtfs_without_stop_times.zip"),
+            GtfsReader::default().read("fixtures/without_stop_times/"),
+        ] {
+            match result.as_ref().err().unwrap() {
+                GtfsError::MissingFile(name) => assert_eq!("stop_times.txt", name),
+                err => panic!("unexpected error {}", err),
+            }
+        }
+
+        let reader = GtfsReader::default().require_stop_times(false);
+        let zip = reader
+            .read_from_zip("fixtures/gtfs_without_stop_times.zip")
+            .unwrap();
+        let dir = reader.read("fixtures/without_stop_times/").unwrap();
+        for gtfs in &[zip, dir] {
+            assert_eq!(5, gtfs.stops.len());
+            assert_eq!(1, gtfs.trips.len());
+            assert!(gtfs.get_trip("trip1").unwrap().stop_times.is_empty());
+        }
+    }
+
+    #[test]
+    fn read_split_stop_times() {
+        let gtfs = Gtfs::from_zip("fixtures/gtfs_split_stop_times.zip").unwrap();
+        let stops: Vec<&str> = gtfs
+            .get_trip("trip1")
+            .unwrap()
+            .stop_times
+            .iter()
+            .map(|st| st.stop.id.as_str())
+            .collect();
+        assert_eq!(vec!["stop2", "stop3"], stops);
+        assert!(is_stop_times_file("feed/stop_times.txt"));
+        assert!(!is_stop_times_file("stops.txt"));
+    }
+
     #[test]
     fn read_from_subdirectory() {
         let gtfs = Gtfs::from_zip("fixtures/subdirectory.zip").unwrap();